@@ -0,0 +1,88 @@
+use std::{
+    sync::{atomic::AtomicBool, Arc},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    board::{Board, START_FEN},
+    search::{Limits, Pruning},
+    smp,
+    transposition_table::{TranspositionTable, DEFAULT_SIZE_MB},
+};
+
+/// Openings, middlegames and endgames, so the bench covers every phase of the game.
+const POSITIONS: [&str; 8] = [
+    START_FEN,
+    "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+    "r1bqkb1r/pppp1ppp/2n2n2/4p3/2B1P3/5N2/PPPP1PPP/RNBQK2R w KQkq - 4 4",
+    "rnbq1rk1/ppp1bppp/4pn2/3p4/2PP4/2N2N2/PP2PPPP/R1BQKB1R w KQ - 4 6",
+    "r2q1rk1/pp2bppp/2n1pn2/3p4/3P1B2/2PBPN2/PP1N1PPP/R2QK2R w KQ - 5 9",
+    "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+    "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1",
+    "8/8/4k3/8/2p5/8/B2K4/8 w - - 0 1",
+];
+
+pub struct BenchResult {
+    pub nodes: u64,
+    pub elapsed: Duration,
+}
+
+impl BenchResult {
+    pub fn nps(&self) -> u64 {
+        return (self.nodes as f64 / self.elapsed.as_secs_f64().max(0.001)) as u64;
+    }
+}
+
+/// Searches every bench position to `depth` with `threads` threads, each from an
+/// empty transposition table, and adds up the nodes and time.
+pub fn bench(depth: u32, threads: usize) -> BenchResult {
+    let mut table = TranspositionTable::new(DEFAULT_SIZE_MB);
+    let limits = Limits {
+        depth: Some(depth),
+        ..Limits::default()
+    };
+    let mut nodes = 0;
+    let start = Instant::now();
+
+    for fen in POSITIONS {
+        let board = Board::from_fen(fen).unwrap();
+        table.clear();
+
+        let results = smp::search(
+            &board,
+            limits.clone(),
+            Pruning::default(),
+            threads,
+            Arc::new(AtomicBool::new(false)),
+            &mut table,
+            |_| (),
+        );
+        nodes += results.last().map_or(0, |result| result.nodes);
+    }
+
+    return BenchResult {
+        nodes,
+        elapsed: start.elapsed(),
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bench_positions_are_valid() {
+        for fen in POSITIONS {
+            assert!(Board::from_fen(fen).is_ok(), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn bench_runs_with_one_and_more_threads() {
+        let single = bench(2, 1);
+        let multi = bench(2, 2);
+
+        assert!(single.nodes > 0);
+        assert!(multi.nodes > 0);
+    }
+}
@@ -1,7 +1,150 @@
 use crate::bitboard::BitManipulation;
-use crate::bitboard::Bitboard;
+use crate::bitboard::{Bitboard, RANK1, RANK8};
+use crate::move_generation::is_square_attacked;
 use crate::piece::Pieces;
+use crate::r#move::Move;
+use crate::zobrist;
 
+pub const START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+const WHITE_KING_START: u32 = 60;
+const BLACK_KING_START: u32 = 4;
+const WHITE_QUEEN_SIDE_ROOK: u32 = 56;
+const WHITE_KING_SIDE_ROOK: u32 = 63;
+const BLACK_QUEEN_SIDE_ROOK: u32 = 0;
+const BLACK_KING_SIDE_ROOK: u32 = 7;
+
+pub const PIECES: [Pieces; 6] = [
+    Pieces::Pawns,
+    Pieces::Rooks,
+    Pieces::Bishops,
+    Pieces::Knights,
+    Pieces::Kings,
+    Pieces::Queens,
+];
+
+#[derive(Debug, PartialEq)]
+pub enum FenError {
+    WrongFieldCount(usize),
+    WrongRankCount(usize),
+    WrongRankLength { rank: usize, squares: u32 },
+    InvalidPiece(char),
+    InvalidSideToMove(String),
+    InvalidCastling(String),
+    InvalidEnPassant(String),
+    InvalidHalfMoves(String),
+    InvalidFullMoves(String),
+}
+
+impl std::fmt::Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            FenError::WrongFieldCount(count) => {
+                write!(f, "expected 6 FEN fields, found {}", count)
+            }
+            FenError::WrongRankCount(count) => {
+                write!(f, "expected 8 ranks in piece placement, found {}", count)
+            }
+            FenError::WrongRankLength { rank, squares } => {
+                write!(
+                    f,
+                    "rank {} describes {} squares instead of 8",
+                    rank, squares
+                )
+            }
+            FenError::InvalidPiece(piece) => write!(f, "invalid piece character '{}'", piece),
+            FenError::InvalidSideToMove(side) => write!(f, "invalid side to move '{}'", side),
+            FenError::InvalidCastling(castling) => {
+                write!(f, "invalid castling rights '{}'", castling)
+            }
+            FenError::InvalidEnPassant(square) => {
+                write!(f, "invalid en passant square '{}'", square)
+            }
+            FenError::InvalidHalfMoves(clock) => write!(f, "invalid half move clock '{}'", clock),
+            FenError::InvalidFullMoves(clock) => write!(f, "invalid full move number '{}'", clock),
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+/// Reasons a parsed position can't come up in a real game.
+#[derive(Debug, PartialEq)]
+pub enum PositionError {
+    WrongKingCount { white: bool, count: u32 },
+    PawnOnBackRank(u32),
+    SideNotToMoveInCheck,
+    CastlingWithoutKing(char),
+    CastlingWithoutRook(char),
+    InvalidEnPassant(u32),
+    TooManyPieces { white: bool, count: u32 },
+    TooManyPawns { white: bool, count: u32 },
+    TooManyPromotedPieces { white: bool },
+}
+
+fn color_name(white: bool) -> &'static str {
+    return if white { "white" } else { "black" };
+}
+
+impl std::fmt::Display for PositionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        match self {
+            PositionError::WrongKingCount { white, count } => {
+                write!(f, "{} has {} kings instead of 1", color_name(*white), count)
+            }
+            PositionError::PawnOnBackRank(square) => {
+                write!(f, "pawn on back rank square {}", num_to_square(*square))
+            }
+            PositionError::SideNotToMoveInCheck => {
+                write!(f, "the side not to move is in check")
+            }
+            PositionError::CastlingWithoutKing(right) => {
+                write!(
+                    f,
+                    "castling right '{}' without a king on its home square",
+                    right
+                )
+            }
+            PositionError::CastlingWithoutRook(right) => {
+                write!(
+                    f,
+                    "castling right '{}' without a rook on its home square",
+                    right
+                )
+            }
+            PositionError::InvalidEnPassant(square) => write!(
+                f,
+                "en passant square {} doesn't follow a double pawn push",
+                num_to_square(*square)
+            ),
+            PositionError::TooManyPieces { white, count } => {
+                write!(
+                    f,
+                    "{} has {} pieces, at most 16 are possible",
+                    color_name(*white),
+                    count
+                )
+            }
+            PositionError::TooManyPawns { white, count } => {
+                write!(
+                    f,
+                    "{} has {} pawns, at most 8 are possible",
+                    color_name(*white),
+                    count
+                )
+            }
+            PositionError::TooManyPromotedPieces { white } => write!(
+                f,
+                "{} has more promoted pieces than missing pawns",
+                color_name(*white)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PositionError {}
+
+#[derive(Clone)]
 pub struct Board {
     pub game_state: GameState,
 
@@ -9,9 +152,14 @@ pub struct Board {
 
     pub black_pieces: Bitboard,
     pub white_pieces: Bitboard,
+
+    /// Zobrist key of the position, kept up to date by `make_move`/`unmake_move`.
+    pub hash: u64,
+    /// Zobrist key of just the pawns, for caching pawn structure evaluation.
+    pub pawn_hash: u64,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct GameState {
     pub white_turn: bool,
     pub en_passant: Option<u32>,
@@ -20,7 +168,7 @@ pub struct GameState {
     pub full_moves: u32,
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug)]
 pub struct Castling {
     pub white_queen_side: bool,
     pub white_king_side: bool,
@@ -28,6 +176,13 @@ pub struct Castling {
     pub black_king_side: bool,
 }
 
+/// Everything needed to take a move back that can't be derived from the move itself.
+#[derive(Clone, Debug)]
+pub struct Undo {
+    pub captured: Option<Pieces>,
+    pub game_state: GameState,
+}
+
 impl Board {
     pub fn new() -> Self {
         Board {
@@ -55,39 +210,16 @@ impl Board {
 
             black_pieces: 0 as Bitboard,
             white_pieces: 0 as Bitboard,
+
+            hash: 0,
+            pawn_hash: 0,
         }
     }
 
-    fn add_piece(mut self, piece: &char, index: u8) -> Board {
-        match piece {
-            'p' | 'P' => {
-                self.pieces[Pieces::Pawns as usize] =
-                    self.pieces[Pieces::Pawns as usize].toggle_square(index)
-            }
-            'r' | 'R' => {
-                self.pieces[Pieces::Rooks as usize] =
-                    self.pieces[Pieces::Rooks as usize].toggle_square(index)
-            }
-            'n' | 'N' => {
-                self.pieces[Pieces::Knights as usize] =
-                    self.pieces[Pieces::Knights as usize].toggle_square(index)
-            }
-            'b' | 'B' => {
-                self.pieces[Pieces::Bishops as usize] =
-                    self.pieces[Pieces::Bishops as usize].toggle_square(index)
-            }
-            'q' | 'Q' => {
-                self.pieces[Pieces::Queens as usize] =
-                    self.pieces[Pieces::Queens as usize].toggle_square(index)
-            }
-            'k' | 'K' => {
-                self.pieces[Pieces::Kings as usize] =
-                    self.pieces[Pieces::Kings as usize].toggle_square(index)
-            }
-            _ => println!("Unresolved character found"),
-        }
+    fn add_piece(mut self, piece: Pieces, white: bool, index: u8) -> Board {
+        self.pieces[piece as usize] = self.pieces[piece as usize].toggle_square(index);
 
-        if piece.is_uppercase() {
+        if white {
             self.white_pieces = self.white_pieces.toggle_square(index);
         } else {
             self.black_pieces = self.black_pieces.toggle_square(index);
@@ -96,74 +228,101 @@ impl Board {
         return self;
     }
 
-    pub fn from_fen(fen_string: &str) -> Board {
+    pub fn from_fen(fen_string: &str) -> Result<Board, FenError> {
         let fen_parts: Vec<&str> = fen_string.split_whitespace().collect();
         let mut board = Board::new();
 
-        let pieces = fen_parts.first().expect("Not valid FEN string");
-        let pieces: Vec<char> = pieces.replace('/', "").chars().collect();
+        if fen_parts.len() != 6 {
+            return Err(FenError::WrongFieldCount(fen_parts.len()));
+        }
+
+        let ranks: Vec<&str> = fen_parts[0].split('/').collect();
+        if ranks.len() != 8 {
+            return Err(FenError::WrongRankCount(ranks.len()));
+        }
+
         let mut square_index: u8 = 0;
 
-        for piece in pieces {
-            match piece {
-                'r' | 'n' | 'b' | 'q' | 'k' | 'p' => {
-                    board = board.add_piece(&piece, square_index);
-                    square_index += 1;
-                }
-                'R' | 'N' | 'B' | 'Q' | 'K' | 'P' => {
-                    board = board.add_piece(&piece, square_index);
-                    square_index += 1;
-                }
-                '1'..='8' => {
-                    let num = piece.to_digit(10).unwrap();
-                    square_index += num as u8;
+        for (rank_index, rank) in ranks.iter().enumerate() {
+            let mut squares_in_rank = 0;
+
+            for piece in rank.chars() {
+                match piece {
+                    '1'..='8' => {
+                        let num = piece.to_digit(10).unwrap();
+                        squares_in_rank += num;
+                    }
+                    _ => {
+                        let (piece, white) =
+                            char_to_piece(piece).ok_or(FenError::InvalidPiece(piece))?;
+                        if squares_in_rank < 8 {
+                            let index = square_index + squares_in_rank as u8;
+                            board = board.add_piece(piece, white, index);
+                        }
+                        squares_in_rank += 1;
+                    }
                 }
-                _ => println!("Unkown character found"),
             }
-        }
 
-        let is_white_active_color = fen_parts
-            .get(1)
-            .expect("Invalid FEN string. Active color missing.")
-            .eq(&"w");
+            if squares_in_rank != 8 {
+                return Err(FenError::WrongRankLength {
+                    rank: 8 - rank_index,
+                    squares: squares_in_rank,
+                });
+            }
 
-        let castling_rights = fen_parts
-            .get(2)
-            .expect("Invalid FEN string. Castling rights missing");
+            square_index += 8;
+        }
 
-        let castling_right_white_king = castling_rights.contains("K");
-        let castling_right_white_queen = castling_rights.contains("Q");
+        let is_white_active_color = match fen_parts[1] {
+            "w" => true,
+            "b" => false,
+            side => return Err(FenError::InvalidSideToMove(side.to_string())),
+        };
 
-        let castling_right_black_king = castling_rights.contains("k");
-        let castling_right_black_queen = castling_rights.contains("q");
+        let castling_rights = fen_parts[2];
+        let castling_rights = if castling_rights == "-" {
+            Castling {
+                white_king_side: false,
+                white_queen_side: false,
+                black_king_side: false,
+                black_queen_side: false,
+            }
+        } else {
+            let all_known = castling_rights.chars().all(|right| "KQkq".contains(right));
+            let no_duplicates = "KQkq"
+                .chars()
+                .all(|right| castling_rights.matches(right).count() <= 1);
 
-        let castling_rights = Castling {
-            white_king_side: castling_right_white_king,
-            white_queen_side: castling_right_white_queen,
-            black_king_side: castling_right_black_king,
-            black_queen_side: castling_right_black_queen,
+            if !all_known || !no_duplicates {
+                return Err(FenError::InvalidCastling(castling_rights.to_string()));
+            }
+
+            Castling {
+                white_king_side: castling_rights.contains('K'),
+                white_queen_side: castling_rights.contains('Q'),
+                black_king_side: castling_rights.contains('k'),
+                black_queen_side: castling_rights.contains('q'),
+            }
         };
 
-        let en_passant = fen_parts
-            .get(3)
-            .expect("Invalid FEN string. En passant missing");
-        let en_passant_square_num: Option<u32> = if en_passant.contains('-') {
+        let en_passant = fen_parts[3];
+        let en_passant_square_num: Option<u32> = if en_passant == "-" {
             None
         } else {
-            Some(square_to_num(en_passant.to_string()))
+            match square_to_num(en_passant) {
+                Some(square) => Some(square),
+                None => return Err(FenError::InvalidEnPassant(en_passant.to_string())),
+            }
         };
 
-        let half_moves: u32 = fen_parts
-            .get(4)
-            .expect("Invalid FEN string. Half moves missing")
+        let half_moves: u32 = fen_parts[4]
             .parse()
-            .unwrap();
+            .map_err(|_| FenError::InvalidHalfMoves(fen_parts[4].to_string()))?;
 
-        let full_moves: u32 = fen_parts
-            .get(5)
-            .expect("Invalid FEN string. Full moves missing")
+        let full_moves: u32 = fen_parts[5]
             .parse()
-            .unwrap();
+            .map_err(|_| FenError::InvalidFullMoves(fen_parts[5].to_string()))?;
 
         board.game_state = GameState {
             white_turn: is_white_active_color,
@@ -172,19 +331,416 @@ impl Board {
             half_moves,
             full_moves,
         };
+        board.hash = zobrist::hash(&board);
+        board.pawn_hash = zobrist::pawn_hash(&board);
+
+        return Ok(board);
+    }
+
+    /// Checks that the position could have come up in a game. `from_fen` only checks
+    /// the syntax, so positions from outside should go through here as well.
+    pub fn validate(&self) -> Result<(), PositionError> {
+        for white in [true, false] {
+            let own_pieces = if white {
+                self.white_pieces
+            } else {
+                self.black_pieces
+            };
+            let count = |piece: Pieces| (self.pieces[piece as usize] & own_pieces).count_ones();
+
+            let kings = count(Pieces::Kings);
+            if kings != 1 {
+                return Err(PositionError::WrongKingCount {
+                    white,
+                    count: kings,
+                });
+            }
+
+            let pieces = own_pieces.count_ones();
+            if pieces > 16 {
+                return Err(PositionError::TooManyPieces {
+                    white,
+                    count: pieces,
+                });
+            }
+
+            let pawns = count(Pieces::Pawns);
+            if pawns > 8 {
+                return Err(PositionError::TooManyPawns {
+                    white,
+                    count: pawns,
+                });
+            }
+
+            // Every piece beyond the starting set has to be a promoted pawn
+            let promoted = count(Pieces::Queens).saturating_sub(1)
+                + count(Pieces::Rooks).saturating_sub(2)
+                + count(Pieces::Bishops).saturating_sub(2)
+                + count(Pieces::Knights).saturating_sub(2);
+            if promoted > 8 - pawns {
+                return Err(PositionError::TooManyPromotedPieces { white });
+            }
+        }
+
+        let back_rank_pawns = self.pieces[Pieces::Pawns as usize] & (RANK1 | RANK8);
+        if back_rank_pawns > 0 {
+            return Err(PositionError::PawnOnBackRank(
+                back_rank_pawns.trailing_zeros(),
+            ));
+        }
+
+        let white_turn = self.game_state.white_turn;
+        let waiting_king = self.pieces[Pieces::Kings as usize]
+            & if white_turn {
+                self.black_pieces
+            } else {
+                self.white_pieces
+            };
+        if is_square_attacked(self, waiting_king.trailing_zeros(), white_turn) {
+            return Err(PositionError::SideNotToMoveInCheck);
+        }
+
+        let castling = &self.game_state.castling;
+        for (allowed, right, white, king_square, rook_square) in [
+            (
+                castling.white_king_side,
+                'K',
+                true,
+                WHITE_KING_START,
+                WHITE_KING_SIDE_ROOK,
+            ),
+            (
+                castling.white_queen_side,
+                'Q',
+                true,
+                WHITE_KING_START,
+                WHITE_QUEEN_SIDE_ROOK,
+            ),
+            (
+                castling.black_king_side,
+                'k',
+                false,
+                BLACK_KING_START,
+                BLACK_KING_SIDE_ROOK,
+            ),
+            (
+                castling.black_queen_side,
+                'q',
+                false,
+                BLACK_KING_START,
+                BLACK_QUEEN_SIDE_ROOK,
+            ),
+        ] {
+            if !allowed {
+                continue;
+            }
+
+            let own_pieces = if white {
+                self.white_pieces
+            } else {
+                self.black_pieces
+            };
+
+            if self.pieces[Pieces::Kings as usize] & own_pieces & (1 << king_square) == 0 {
+                return Err(PositionError::CastlingWithoutKing(right));
+            }
+            if self.pieces[Pieces::Rooks as usize] & own_pieces & (1 << rook_square) == 0 {
+                return Err(PositionError::CastlingWithoutRook(right));
+            }
+        }
+
+        if let Some(en_passant) = self.game_state.en_passant {
+            // The pawn that just moved passed over the en passant square, coming from
+            // the square behind it and landing on the square in front of it.
+            let (rank, pushed_pawn, start_square, enemy_pieces) = if white_turn {
+                (
+                    2,
+                    en_passant + 8,
+                    en_passant.wrapping_sub(8),
+                    self.black_pieces,
+                )
+            } else {
+                (
+                    5,
+                    en_passant.wrapping_sub(8),
+                    en_passant + 8,
+                    self.white_pieces,
+                )
+            };
+
+            let occupied = self.white_pieces | self.black_pieces;
+            let pawns = self.pieces[Pieces::Pawns as usize] & enemy_pieces;
+
+            if en_passant / 8 != rank
+                || pawns & (1 << pushed_pawn) == 0
+                || occupied & (1 << en_passant | 1 << start_square) > 0
+            {
+                return Err(PositionError::InvalidEnPassant(en_passant));
+            }
+        }
+
+        return Ok(());
+    }
+
+    pub fn to_fen(&self) -> String {
+        let mut placement = String::new();
+
+        for rank in 0..8 {
+            let mut empty_squares = 0;
+
+            for file in 0..8 {
+                let square = rank * 8 + file;
+
+                match self.piece_at(square) {
+                    Some(piece) => {
+                        if empty_squares > 0 {
+                            placement.push_str(&empty_squares.to_string());
+                            empty_squares = 0;
+                        }
+                        let white = self.white_pieces & (1 << square) > 0;
+                        placement.push(piece_to_char(piece, white));
+                    }
+                    None => empty_squares += 1,
+                }
+            }
+
+            if empty_squares > 0 {
+                placement.push_str(&empty_squares.to_string());
+            }
+            if rank < 7 {
+                placement.push('/');
+            }
+        }
+
+        let active_color = if self.game_state.white_turn { "w" } else { "b" };
+
+        let castling = &self.game_state.castling;
+        let mut castling_rights = String::new();
+        for (allowed, right) in [
+            (castling.white_king_side, 'K'),
+            (castling.white_queen_side, 'Q'),
+            (castling.black_king_side, 'k'),
+            (castling.black_queen_side, 'q'),
+        ] {
+            if allowed {
+                castling_rights.push(right);
+            }
+        }
+        if castling_rights.is_empty() {
+            castling_rights.push('-');
+        }
+
+        let en_passant = match self.game_state.en_passant {
+            Some(square) => num_to_square(square),
+            None => "-".to_string(),
+        };
+
+        return format!(
+            "{} {} {} {} {} {}",
+            placement,
+            active_color,
+            castling_rights,
+            en_passant,
+            self.game_state.half_moves,
+            self.game_state.full_moves
+        );
+    }
+
+    pub fn piece_at(&self, square: u32) -> Option<Pieces> {
+        let square_bitboard: Bitboard = 1 << square;
+
+        return PIECES
+            .into_iter()
+            .find(|&piece| self.pieces[piece as usize] & square_bitboard > 0);
+    }
+
+    fn toggle_piece(&mut self, piece: Pieces, square: u32, white: bool) {
+        let square_bitboard: Bitboard = 1 << square;
+
+        self.pieces[piece as usize] ^= square_bitboard;
+        self.hash ^= zobrist::piece_key(piece, white, square);
+        if piece == Pieces::Pawns {
+            self.pawn_hash ^= zobrist::piece_key(piece, white, square);
+        }
+        if white {
+            self.white_pieces ^= square_bitboard;
+        } else {
+            self.black_pieces ^= square_bitboard;
+        }
+    }
+
+    /// The square of the pawn removed by an en passant capture landing on `to`.
+    pub fn en_passant_victim(to: u32, white_turn: bool) -> u32 {
+        if white_turn {
+            to + 8
+        } else {
+            to - 8
+        }
+    }
+
+    /// Rook origin and destination for a castling king landing on `king_to`.
+    pub fn castling_rook_squares(king_to: u32) -> (u32, u32) {
+        match king_to {
+            62 => (WHITE_KING_SIDE_ROOK, 61),
+            58 => (WHITE_QUEEN_SIDE_ROOK, 59),
+            6 => (BLACK_KING_SIDE_ROOK, 5),
+            2 => (BLACK_QUEEN_SIDE_ROOK, 3),
+            _ => panic!("Invalid castling destination"),
+        }
+    }
+
+    pub fn is_castling(r#move: &Move) -> bool {
+        return r#move.piece == Pieces::Kings && r#move.from.abs_diff(r#move.to) == 2;
+    }
+
+    pub fn is_en_passant(&self, r#move: &Move) -> bool {
+        return r#move.piece == Pieces::Pawns
+            && self.game_state.en_passant == Some(r#move.to)
+            && r#move.from % 8 != r#move.to % 8;
+    }
+
+    /// Plays `move` for the side to move. The returned record has to be passed to
+    /// `unmake_move` to restore the position exactly.
+    pub fn make_move(&mut self, r#move: &Move) -> Undo {
+        let white_turn = self.game_state.white_turn;
+        let undo_game_state = self.game_state.clone();
+        self.hash ^= zobrist::state_key(&self.game_state);
+
+        let captured = if self.is_en_passant(r#move) {
+            let victim = Board::en_passant_victim(r#move.to, white_turn);
+            self.toggle_piece(Pieces::Pawns, victim, !white_turn);
+            Some(Pieces::Pawns)
+        } else if let Some(captured) = self.piece_at(r#move.to) {
+            self.toggle_piece(captured, r#move.to, !white_turn);
+            Some(captured)
+        } else {
+            None
+        };
+
+        self.toggle_piece(r#move.piece, r#move.from, white_turn);
+        self.toggle_piece(r#move.piece, r#move.to, white_turn);
+
+        if let Some(promotion) = r#move.promotion {
+            self.toggle_piece(r#move.piece, r#move.to, white_turn);
+            self.toggle_piece(promotion, r#move.to, white_turn);
+        }
+
+        if Board::is_castling(r#move) {
+            let (rook_from, rook_to) = Board::castling_rook_squares(r#move.to);
+            self.toggle_piece(Pieces::Rooks, rook_from, white_turn);
+            self.toggle_piece(Pieces::Rooks, rook_to, white_turn);
+        }
+
+        let castling = &mut self.game_state.castling;
+        for square in [r#move.from, r#move.to] {
+            match square {
+                WHITE_KING_START => {
+                    castling.white_king_side = false;
+                    castling.white_queen_side = false;
+                }
+                BLACK_KING_START => {
+                    castling.black_king_side = false;
+                    castling.black_queen_side = false;
+                }
+                WHITE_KING_SIDE_ROOK => castling.white_king_side = false,
+                WHITE_QUEEN_SIDE_ROOK => castling.white_queen_side = false,
+                BLACK_KING_SIDE_ROOK => castling.black_king_side = false,
+                BLACK_QUEEN_SIDE_ROOK => castling.black_queen_side = false,
+                _ => (),
+            }
+        }
+
+        self.game_state.en_passant =
+            if r#move.piece == Pieces::Pawns && r#move.from.abs_diff(r#move.to) == 16 {
+                Some((r#move.from + r#move.to) / 2)
+            } else {
+                None
+            };
+
+        if r#move.piece == Pieces::Pawns || captured.is_some() {
+            self.game_state.half_moves = 0;
+        } else {
+            self.game_state.half_moves += 1;
+        }
 
-        return board;
+        if !white_turn {
+            self.game_state.full_moves += 1;
+        }
+
+        self.game_state.white_turn = !white_turn;
+        self.hash ^= zobrist::state_key(&self.game_state);
+
+        return Undo {
+            captured,
+            game_state: undo_game_state,
+        };
+    }
+
+    /// Takes back `move`, which must be the last move played with `make_move`.
+    pub fn unmake_move(&mut self, r#move: &Move, undo: Undo) {
+        self.hash ^= zobrist::state_key(&self.game_state) ^ zobrist::state_key(&undo.game_state);
+        self.game_state = undo.game_state;
+        let white_turn = self.game_state.white_turn;
+
+        if Board::is_castling(r#move) {
+            let (rook_from, rook_to) = Board::castling_rook_squares(r#move.to);
+            self.toggle_piece(Pieces::Rooks, rook_to, white_turn);
+            self.toggle_piece(Pieces::Rooks, rook_from, white_turn);
+        }
+
+        if let Some(promotion) = r#move.promotion {
+            self.toggle_piece(promotion, r#move.to, white_turn);
+            self.toggle_piece(r#move.piece, r#move.to, white_turn);
+        }
+
+        self.toggle_piece(r#move.piece, r#move.to, white_turn);
+        self.toggle_piece(r#move.piece, r#move.from, white_turn);
+
+        if let Some(captured) = undo.captured {
+            let square = if self.is_en_passant(r#move) {
+                Board::en_passant_victim(r#move.to, white_turn)
+            } else {
+                r#move.to
+            };
+            self.toggle_piece(captured, square, !white_turn);
+        }
+    }
+
+    /// Passes the turn without moving, for null-move pruning. Any en passant right
+    /// is lost, as after a real move.
+    pub fn make_null_move(&mut self) -> Undo {
+        let undo_game_state = self.game_state.clone();
+        self.hash ^= zobrist::state_key(&self.game_state);
+
+        if !self.game_state.white_turn {
+            self.game_state.full_moves += 1;
+        }
+        self.game_state.en_passant = None;
+        self.game_state.half_moves += 1;
+        self.game_state.white_turn = !self.game_state.white_turn;
+        self.hash ^= zobrist::state_key(&self.game_state);
+
+        return Undo {
+            captured: None,
+            game_state: undo_game_state,
+        };
+    }
+
+    /// Takes back the null move `make_null_move` returned `undo` for.
+    pub fn unmake_null_move(&mut self, undo: Undo) {
+        self.hash ^= zobrist::state_key(&self.game_state) ^ zobrist::state_key(&undo.game_state);
+        self.game_state = undo.game_state;
     }
 }
 
-fn square_to_num(square: String) -> u32 {
-    let chars: Vec<char> = square.chars().collect();
-    let col_char = chars.get(0).expect("Invalid square");
-    let row_num = chars
-        .get(1)
-        .expect("Invalid square")
-        .to_digit(10)
-        .expect("Invalid square");
+fn square_to_num(square: &str) -> Option<u32> {
+    let mut chars = square.chars();
+    let col_char = chars.next()?;
+    let row_num = chars.next()?.to_digit(10)?;
+
+    if chars.next().is_some() || !(1..=8).contains(&row_num) {
+        return None;
+    }
 
     let col_num = match col_char {
         'a' => 0,
@@ -195,11 +751,48 @@ fn square_to_num(square: String) -> u32 {
         'f' => 5,
         'g' => 6,
         'h' => 7,
-        'i' => 8,
-        _ => panic!("Invalid square num"),
+        _ => return None,
+    };
+
+    return Some((64 - (row_num * 8)) + col_num);
+}
+
+pub fn num_to_square(square: u32) -> String {
+    let file = (b'a' + (square % 8) as u8) as char;
+    let rank = 8 - square / 8;
+
+    return format!("{file}{rank}");
+}
+
+fn char_to_piece(piece: char) -> Option<(Pieces, bool)> {
+    let piece_type = match piece.to_ascii_lowercase() {
+        'p' => Pieces::Pawns,
+        'r' => Pieces::Rooks,
+        'n' => Pieces::Knights,
+        'b' => Pieces::Bishops,
+        'q' => Pieces::Queens,
+        'k' => Pieces::Kings,
+        _ => return None,
+    };
+
+    return Some((piece_type, piece.is_ascii_uppercase()));
+}
+
+fn piece_to_char(piece: Pieces, white: bool) -> char {
+    let piece_char = match piece {
+        Pieces::Pawns => 'p',
+        Pieces::Rooks => 'r',
+        Pieces::Knights => 'n',
+        Pieces::Bishops => 'b',
+        Pieces::Queens => 'q',
+        Pieces::Kings => 'k',
     };
 
-    return (64 - (row_num * 8)) + col_num;
+    return if white {
+        piece_char.to_ascii_uppercase()
+    } else {
+        piece_char
+    };
 }
 
 fn map_bitboard_to_string(
@@ -280,10 +873,12 @@ impl std::fmt::Display for Board {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{move_generation::legal_moves, test_util::next_random};
 
     #[test]
     fn from_fen_starting_pos() {
-        let board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        let board =
+            Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
 
         assert_eq!(
             0b0000000011111111000000000000000000000000000000001111111100000000,
@@ -336,7 +931,8 @@ mod tests {
 
     #[test]
     fn from_fen_mid_game() {
-        let board = Board::from_fen("r1bk3r/p2pBpNp/n4n2/1p1NP2P/6P1/3P4/P1P1K3/q5b1 b KQkq - 0 1");
+        let board = Board::from_fen("r1bk3r/p2pBpNp/n4n2/1p1NP2P/6P1/3P4/P1P1K3/q5b1 b KQkq - 0 1")
+            .unwrap();
 
         assert_eq!(
             0b0000000000000101000010000100000010010010000000001010100100000000,
@@ -388,8 +984,9 @@ mod tests {
 
     #[test]
     fn en_passant_and_castling_rights() {
-        let board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQ c5 30 44");
-        
+        let board =
+            Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQ c5 30 44").unwrap();
+
         let expected_game_state = GameState {
             white_turn: false,
             castling: Castling {
@@ -409,20 +1006,375 @@ mod tests {
     fn board_display() {
         let result_string = "  a b c d e f g h\n8 r n b q k b n r \n7 p p p p p p p p \n6 . . . . . . . . \n5 . . . . . . . . \n4 . . . . . . . . \n3 . . . . . . . . \n2 P P P P P P P P \n1 R N B Q K B N R \n";
 
-        let board = Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+        let board =
+            Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
 
         assert_eq!(result_string, format!("{board}"));
     }
 
+    fn assert_same_position(board: &Board, other: &Board) {
+        assert_eq!(board.pieces, other.pieces);
+        assert_eq!(board.white_pieces, other.white_pieces);
+        assert_eq!(board.black_pieces, other.black_pieces);
+        assert_eq!(board.game_state, other.game_state);
+        assert_eq!(board.hash, other.hash);
+        assert_eq!(board.pawn_hash, other.pawn_hash);
+    }
+
+    #[test]
+    fn make_and_unmake_double_push() {
+        let mut board =
+            Board::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap();
+        let original = board.clone();
+
+        let r#move = Move {
+            from: 52,
+            to: 36,
+            piece: Pieces::Pawns,
+            promotion: None,
+        };
+        let undo = board.make_move(&r#move);
+
+        let expected =
+            Board::from_fen("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1").unwrap();
+        assert_same_position(&board, &expected);
+
+        board.unmake_move(&r#move, undo);
+        assert_same_position(&board, &original);
+    }
+
+    #[test]
+    fn make_and_unmake_capture() {
+        let mut board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 5 20").unwrap();
+        let original = board.clone();
+
+        let r#move = Move {
+            from: 0,
+            to: 56,
+            piece: Pieces::Rooks,
+            promotion: None,
+        };
+        let undo = board.make_move(&r#move);
+
+        let expected = Board::from_fen("4k2r/8/8/8/8/8/8/r3K2R w Kk - 0 21").unwrap();
+        assert_same_position(&board, &expected);
+
+        board.unmake_move(&r#move, undo);
+        assert_same_position(&board, &original);
+    }
+
+    #[test]
+    fn make_and_unmake_en_passant() {
+        let mut board =
+            Board::from_fen("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3")
+                .unwrap();
+        let original = board.clone();
+
+        let r#move = Move {
+            from: 28,
+            to: 21,
+            piece: Pieces::Pawns,
+            promotion: None,
+        };
+        let undo = board.make_move(&r#move);
+
+        let expected =
+            Board::from_fen("rnbqkbnr/ppp1p1pp/5P2/3p4/8/8/PPPP1PPP/RNBQKBNR b KQkq - 0 3")
+                .unwrap();
+        assert_same_position(&board, &expected);
+
+        board.unmake_move(&r#move, undo);
+        assert_same_position(&board, &original);
+    }
+
+    #[test]
+    fn make_and_unmake_castling() {
+        let mut board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 3 10").unwrap();
+        let original = board.clone();
+
+        let r#move = Move {
+            from: 60,
+            to: 62,
+            piece: Pieces::Kings,
+            promotion: None,
+        };
+        let undo = board.make_move(&r#move);
+        let expected = Board::from_fen("r3k2r/8/8/8/8/8/8/R4RK1 b kq - 4 10").unwrap();
+        assert_same_position(&board, &expected);
+
+        let black_move = Move {
+            from: 4,
+            to: 2,
+            piece: Pieces::Kings,
+            promotion: None,
+        };
+        let black_undo = board.make_move(&black_move);
+        let expected = Board::from_fen("2kr3r/8/8/8/8/8/8/R4RK1 w - - 5 11").unwrap();
+        assert_same_position(&board, &expected);
+
+        board.unmake_move(&black_move, black_undo);
+        board.unmake_move(&r#move, undo);
+        assert_same_position(&board, &original);
+    }
+
+    #[test]
+    fn make_and_unmake_promotion() {
+        let mut board = Board::from_fen("1n2k3/P7/8/8/8/8/8/4K3 w - - 7 40").unwrap();
+        let original = board.clone();
+
+        let r#move = Move {
+            from: 8,
+            to: 1,
+            piece: Pieces::Pawns,
+            promotion: Some(Pieces::Knights),
+        };
+        let undo = board.make_move(&r#move);
+
+        let expected = Board::from_fen("1N2k3/8/8/8/8/8/8/4K3 b - - 0 40").unwrap();
+        assert_same_position(&board, &expected);
+
+        board.unmake_move(&r#move, undo);
+        assert_same_position(&board, &original);
+    }
+
+    #[test]
+    fn make_and_unmake_null_move() {
+        let mut board =
+            Board::from_fen("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 3")
+                .unwrap();
+        let original = board.clone();
+
+        let undo = board.make_null_move();
+        let expected =
+            Board::from_fen("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR w KQkq - 1 4").unwrap();
+        assert_same_position(&board, &expected);
+
+        board.unmake_null_move(undo);
+        assert_same_position(&board, &original);
+    }
+
+    #[test]
+    fn to_fen_writes_every_field() {
+        for fen in [
+            START_FEN,
+            "r1bk3r/p2pBpNp/n4n2/1p1NP2P/6P1/3P4/P1P1K3/q5b1 b KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQ c6 30 44",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "8/8/8/KPp4r/8/8/8/4k3 w - c6 0 1",
+            "4k3/8/8/8/8/8/8/4K3 b Qk - 99 120",
+        ] {
+            assert_eq!(fen, Board::from_fen(fen).unwrap().to_fen());
+        }
+    }
+
+    #[test]
+    fn fen_round_trip_over_random_games() {
+        let start_positions = [
+            START_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        ];
+
+        let mut random_state: u64 = 0x2545F4914F6CDD1D;
+        let mut positions_checked = 0;
+
+        for fen in start_positions {
+            for _ in 0..40 {
+                let mut board = Board::from_fen(fen).unwrap();
+
+                for _ in 0..60 {
+                    let round_trip = Board::from_fen(&board.to_fen()).unwrap();
+                    assert_same_position(&board, &round_trip);
+                    positions_checked += 1;
+
+                    let moves = legal_moves(&board);
+                    if moves.is_empty() {
+                        break;
+                    }
+                    let r#move = moves[next_random(&mut random_state) as usize % moves.len()];
+                    board.make_move(&r#move);
+                }
+            }
+        }
+
+        assert!(positions_checked > 10_000);
+    }
+
+    #[test]
+    fn validate_accepts_legal_positions() {
+        for fen in [
+            START_FEN,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3",
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            "QQQQQQQQ/Q2k4/8/8/8/8/8/4K3 b - - 0 1",
+        ] {
+            assert_eq!(Ok(()), Board::from_fen(fen).unwrap().validate(), "{fen}");
+        }
+    }
+
+    #[test]
+    fn validate_rejects_illegal_positions() {
+        let cases = [
+            (
+                "rnbq1bnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQ - 0 1",
+                PositionError::WrongKingCount {
+                    white: false,
+                    count: 0,
+                },
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBKKBNR w kq - 0 1",
+                PositionError::WrongKingCount {
+                    white: true,
+                    count: 2,
+                },
+            ),
+            (
+                "rnbqkbnP/pppppppp/8/8/8/8/PPPPPPP1/RNBQKBNR w KQq - 0 1",
+                PositionError::PawnOnBackRank(7),
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K2r b - - 0 1",
+                PositionError::SideNotToMoveInCheck,
+            ),
+            (
+                "4k3/8/8/8/8/8/8/R3K1R1 w K - 0 1",
+                PositionError::CastlingWithoutRook('K'),
+            ),
+            (
+                "r3k2r/8/8/8/8/8/8/R4K1R w Qq - 0 1",
+                PositionError::CastlingWithoutKing('Q'),
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e6 0 1",
+                PositionError::InvalidEnPassant(20),
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e3 0 1",
+                PositionError::InvalidEnPassant(44),
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/4P3/4N3/PPPP1PPP/RNBQKB1R b KQkq e3 0 1",
+                PositionError::InvalidEnPassant(44),
+            ),
+            (
+                "4k3/pppppppp/p7/8/8/8/8/4K3 w - - 0 1",
+                PositionError::TooManyPawns {
+                    white: false,
+                    count: 9,
+                },
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/N7/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                PositionError::TooManyPieces {
+                    white: true,
+                    count: 17,
+                },
+            ),
+            (
+                "4k3/8/8/8/8/QQQ5/PPPPPPP1/4K3 w - - 0 1",
+                PositionError::TooManyPromotedPieces { white: true },
+            ),
+        ];
+
+        for (fen, error) in cases {
+            assert_eq!(
+                Err(error),
+                Board::from_fen(fen).unwrap().validate(),
+                "{fen}"
+            );
+        }
+    }
+
+    #[test]
+    fn transfer_num_to_square() {
+        assert_eq!("a8", num_to_square(0));
+        assert_eq!("e4", num_to_square(36));
+        assert_eq!("h2", num_to_square(55));
+    }
+
     #[test]
     fn transfer_square_to_num() {
-        let num = square_to_num("a8".to_string());
-        assert_eq!(num, 0);
+        let num = square_to_num("a8");
+        assert_eq!(num, Some(0));
 
-        let num = square_to_num("e4".to_string());
-        assert_eq!(num, 36);
+        let num = square_to_num("e4");
+        assert_eq!(num, Some(36));
 
-        let num = square_to_num("h2".to_string());
-        assert_eq!(num, 55);
+        let num = square_to_num("h2");
+        assert_eq!(num, Some(55));
+
+        assert_eq!(square_to_num("i4"), None);
+        assert_eq!(square_to_num("e9"), None);
+        assert_eq!(square_to_num("e"), None);
+        assert_eq!(square_to_num("e44"), None);
+    }
+
+    #[test]
+    fn from_fen_errors() {
+        let cases = [
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq -",
+                FenError::WrongFieldCount(4),
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                FenError::WrongRankCount(7),
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNRR w KQkq - 0 1",
+                FenError::WrongRankLength {
+                    rank: 1,
+                    squares: 9,
+                },
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/3/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+                FenError::WrongRankLength {
+                    rank: 4,
+                    squares: 3,
+                },
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNX w KQkq - 0 1",
+                FenError::InvalidPiece('X'),
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1",
+                FenError::InvalidSideToMove("x".to_string()),
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkx - 0 1",
+                FenError::InvalidCastling("KQkx".to_string()),
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KKq - 0 1",
+                FenError::InvalidCastling("KKq".to_string()),
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq i3 0 1",
+                FenError::InvalidEnPassant("i3".to_string()),
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e 0 1",
+                FenError::InvalidEnPassant("e".to_string()),
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - x 1",
+                FenError::InvalidHalfMoves("x".to_string()),
+            ),
+            (
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 -1",
+                FenError::InvalidFullMoves("-1".to_string()),
+            ),
+        ];
+
+        for (fen, error) in cases {
+            assert_eq!(Board::from_fen(fen).err(), Some(error), "{fen}");
+        }
     }
 }
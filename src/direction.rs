@@ -1,4 +1,4 @@
-use std::{ops::Index, slice::SliceIndex};
+use std::ops::Index;
 
 use crate::bitboard::Bitboard;
 
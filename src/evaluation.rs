@@ -0,0 +1,540 @@
+use std::ops::{Add, AddAssign, Neg, Sub};
+
+use crate::{
+    board::{Board, PIECES},
+    king_safety::king_safety,
+    pawn_structure::{evaluate_pawns, PawnEntry, PawnTable},
+    piece::Pieces,
+    piece_activity::{activity, mobility},
+};
+
+/// A middlegame and an endgame value, blended by game phase at the end.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Score {
+    pub mg: i32,
+    pub eg: i32,
+}
+
+impl Score {
+    pub const fn new(mg: i32, eg: i32) -> Self {
+        Score { mg, eg }
+    }
+}
+
+impl Add for Score {
+    type Output = Score;
+
+    fn add(self, other: Score) -> Score {
+        return Score::new(self.mg + other.mg, self.eg + other.eg);
+    }
+}
+
+impl AddAssign for Score {
+    fn add_assign(&mut self, other: Score) {
+        self.mg += other.mg;
+        self.eg += other.eg;
+    }
+}
+
+impl Sub for Score {
+    type Output = Score;
+
+    fn sub(self, other: Score) -> Score {
+        return Score::new(self.mg - other.mg, self.eg - other.eg);
+    }
+}
+
+impl Neg for Score {
+    type Output = Score;
+
+    fn neg(self) -> Score {
+        return Score::new(-self.mg, -self.eg);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Term {
+    Material,
+    PieceSquare,
+    Mobility,
+    PieceActivity,
+    PawnStructure,
+    KingSafety,
+}
+
+const TERMS: [Term; 6] = [
+    Term::Material,
+    Term::PieceSquare,
+    Term::Mobility,
+    Term::PieceActivity,
+    Term::PawnStructure,
+    Term::KingSafety,
+];
+
+impl Term {
+    fn name(self) -> &'static str {
+        return match self {
+            Term::Material => "Material",
+            Term::PieceSquare => "Piece-square",
+            Term::Mobility => "Mobility",
+            Term::PieceActivity => "Piece activity",
+            Term::PawnStructure => "Pawn structure",
+            Term::KingSafety => "King safety",
+        };
+    }
+}
+
+/// Every evaluation term for each side, before tapering. `evaluate` is computed from
+/// this, so the breakdown always adds up to the real score.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EvalTrace {
+    /// Indexed by term, then 0 for white and 1 for black.
+    pub terms: [[Score; 2]; TERMS.len()],
+    pub phase: i32,
+    pub white_turn: bool,
+}
+
+impl EvalTrace {
+    pub fn get(&self, term: Term, white: bool) -> Score {
+        return self.terms[term as usize][if white { 0 } else { 1 }];
+    }
+
+    fn add(&mut self, term: Term, white: bool, score: Score) {
+        self.terms[term as usize][if white { 0 } else { 1 }] += score;
+    }
+
+    /// White's score minus black's, summed over all terms.
+    pub fn total(&self) -> Score {
+        let mut total = Score::default();
+
+        for term in TERMS {
+            total += self.get(term, true) - self.get(term, false);
+        }
+
+        return total;
+    }
+
+    pub fn taper(&self, score: Score) -> i32 {
+        return (score.mg * self.phase + score.eg * (MAX_PHASE - self.phase)) / MAX_PHASE;
+    }
+
+    /// The tapered total from the side to move's point of view.
+    pub fn score(&self) -> i32 {
+        let score = self.taper(self.total());
+
+        return if self.white_turn { score } else { -score };
+    }
+}
+
+/// A table with one row per term, white and black values and the difference.
+impl std::fmt::Display for EvalTrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        writeln!(
+            f,
+            "{:<15}|{:^15}|{:^15}|{:^15}",
+            "Term", "White", "Black", "Total"
+        )?;
+        writeln!(
+            f,
+            "{:<15}|{:>7}{:>7} |{:>7}{:>7} |{:>7}{:>7}",
+            "", "MG", "EG", "MG", "EG", "MG", "EG"
+        )?;
+        writeln!(f, "{}", "-".repeat(15 + 3 * 16))?;
+
+        for term in TERMS {
+            let white = self.get(term, true);
+            let black = self.get(term, false);
+            let total = white - black;
+
+            writeln!(
+                f,
+                "{:<15}|{:>7}{:>7} |{:>7}{:>7} |{:>7}{:>7}",
+                term.name(),
+                white.mg,
+                white.eg,
+                black.mg,
+                black.eg,
+                total.mg,
+                total.eg
+            )?;
+        }
+
+        let total = self.total();
+        writeln!(f, "{}", "-".repeat(15 + 3 * 16))?;
+        writeln!(
+            f,
+            "{:<15}|{:>15}|{:>15}|{:>7}{:>7}",
+            "Total", "", "", total.mg, total.eg
+        )?;
+        writeln!(f)?;
+        writeln!(f, "Phase: {}/{}", self.phase, MAX_PHASE)?;
+        writeln!(f, "Evaluation (white): {} cp", self.taper(total))?;
+        writeln!(f, "Evaluation (side to move): {} cp", self.score())
+    }
+}
+
+/// Game phase with all pieces on the board. Pawns and kings don't count.
+const MAX_PHASE: i32 = 24;
+
+fn phase_weight(piece: Pieces) -> i32 {
+    return match piece {
+        Pieces::Knights | Pieces::Bishops => 1,
+        Pieces::Rooks => 2,
+        Pieces::Queens => 4,
+        Pieces::Pawns | Pieces::Kings => 0,
+    };
+}
+
+fn piece_value(piece: Pieces) -> Score {
+    return match piece {
+        Pieces::Pawns => Score::new(82, 94),
+        Pieces::Knights => Score::new(337, 281),
+        Pieces::Bishops => Score::new(365, 297),
+        Pieces::Rooks => Score::new(477, 512),
+        Pieces::Queens => Score::new(1025, 936),
+        Pieces::Kings => Score::new(0, 0),
+    };
+}
+
+// Piece-square tables from white's point of view, laid out like the board: index 0 is
+// a8, index 63 is h1. Black looks them up with the rank flipped.
+
+#[rustfmt::skip]
+const MG_PAWN: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+     98, 134,  61,  95,  68, 126,  34, -11,
+     -6,   7,  26,  31,  65,  56,  25, -20,
+    -14,  13,   6,  21,  23,  12,  17, -23,
+    -27,  -2,  -5,  12,  17,   6,  10, -25,
+    -26,  -4,  -4, -10,   3,   3,  33, -12,
+    -35,  -1, -20, -23, -15,  24,  38, -22,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const EG_PAWN: [i32; 64] = [
+      0,   0,   0,   0,   0,   0,   0,   0,
+    178, 173, 158, 134, 147, 132, 165, 187,
+     94, 100,  85,  67,  56,  53,  82,  84,
+     32,  24,  13,   5,  -2,   4,  17,  17,
+     13,   9,  -3,  -7,  -7,  -8,   3,  -1,
+      4,   7,  -6,   1,   0,  -5,  -1,  -8,
+     13,   8,   8,  10,  13,   0,   2,  -7,
+      0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const MG_KNIGHT: [i32; 64] = [
+   -167, -89, -34, -49,  61, -97, -15,-107,
+    -73, -41,  72,  36,  23,  62,   7, -17,
+    -47,  60,  37,  65,  84, 129,  73,  44,
+     -9,  17,  19,  53,  37,  69,  18,  22,
+    -13,   4,  16,  13,  28,  19,  21,  -8,
+    -23,  -9,  12,  10,  19,  17,  25, -16,
+    -29, -53, -12,  -3,  -1,  18, -14, -19,
+   -105, -21, -58, -33, -17, -28, -19, -23,
+];
+
+#[rustfmt::skip]
+const EG_KNIGHT: [i32; 64] = [
+    -58, -38, -13, -28, -31, -27, -63, -99,
+    -25,  -8, -25,  -2,  -9, -25, -24, -52,
+    -24, -20,  10,   9,  -1,  -9, -19, -41,
+    -17,   3,  22,  22,  22,  11,   8, -18,
+    -18,  -6,  16,  25,  16,  17,   4, -18,
+    -23,  -3,  -1,  15,  10,  -3, -20, -22,
+    -42, -20, -10,  -5,  -2, -20, -23, -44,
+    -29, -51, -23, -15, -22, -18, -50, -64,
+];
+
+#[rustfmt::skip]
+const MG_BISHOP: [i32; 64] = [
+    -29,   4, -82, -37, -25, -42,   7,  -8,
+    -26,  16, -18, -13,  30,  59,  18, -47,
+    -16,  37,  43,  40,  35,  50,  37,  -2,
+     -4,   5,  19,  50,  37,  37,   7,  -2,
+     -6,  13,  13,  26,  34,  12,  10,   4,
+      0,  15,  15,  15,  14,  27,  18,  10,
+      4,  15,  16,   0,   7,  21,  33,   1,
+    -33,  -3, -14, -21, -13, -12, -39, -21,
+];
+
+#[rustfmt::skip]
+const EG_BISHOP: [i32; 64] = [
+    -14, -21, -11,  -8,  -7,  -9, -17, -24,
+     -8,  -4,   7, -12,  -3, -13,  -4, -14,
+      2,  -8,   0,  -1,  -2,   6,   0,   4,
+     -3,   9,  12,   9,  14,  10,   3,   2,
+     -6,   3,  13,  19,   7,  10,  -3,  -9,
+    -12,  -3,   8,  10,  13,   3,  -7, -15,
+    -14, -18,  -7,  -1,   4,  -9, -15, -27,
+    -23,  -9, -23,  -5,  -9, -16,  -5, -17,
+];
+
+#[rustfmt::skip]
+const MG_ROOK: [i32; 64] = [
+     32,  42,  32,  51,  63,   9,  31,  43,
+     27,  32,  58,  62,  80,  67,  26,  44,
+     -5,  19,  26,  36,  17,  45,  61,  16,
+    -24, -11,   7,  26,  24,  35,  -8, -20,
+    -36, -26, -12,  -1,   9,  -7,   6, -23,
+    -45, -25, -16, -17,   3,   0,  -5, -33,
+    -44, -16, -20,  -9,  -1,  11,  -6, -71,
+    -19, -13,   1,  17,  16,   7, -37, -26,
+];
+
+#[rustfmt::skip]
+const EG_ROOK: [i32; 64] = [
+     13,  10,  18,  15,  12,  12,   8,   5,
+     11,  13,  13,  11,  -3,   3,   8,   3,
+      7,   7,   7,   5,   4,  -3,  -5,  -3,
+      4,   3,  13,   1,   2,   1,  -1,   2,
+      3,   5,   8,   4,  -5,  -6,  -8, -11,
+     -4,   0,  -5,  -1,  -7, -12,  -8, -16,
+     -6,  -6,   0,   2,  -9,  -9, -11,  -3,
+     -9,   2,   3,  -1,  -5, -13,   4, -20,
+];
+
+#[rustfmt::skip]
+const MG_QUEEN: [i32; 64] = [
+    -28,   0,  29,  12,  59,  44,  43,  45,
+    -24, -39,  -5,   1, -16,  57,  28,  54,
+    -13, -17,   7,   8,  29,  56,  47,  57,
+    -27, -27, -16, -16,  -1,  17,  -2,   1,
+     -9, -26,  -9, -10,  -2,  -4,   3,  -3,
+    -14,   2, -11,  -2,  -5,   2,  14,   5,
+    -35,  -8,  11,   2,   8,  15,  -3,   1,
+     -1, -18,  -9,  10, -15, -25, -31, -50,
+];
+
+#[rustfmt::skip]
+const EG_QUEEN: [i32; 64] = [
+     -9,  22,  22,  27,  27,  19,  10,  20,
+    -17,  20,  32,  41,  58,  25,  30,   0,
+    -20,   6,   9,  49,  47,  35,  19,   9,
+      3,  22,  24,  45,  57,  40,  57,  36,
+    -18,  28,  19,  47,  31,  34,  39,  23,
+    -16, -27,  15,   6,   9,  17,  10,   5,
+    -22, -23, -30, -16, -16, -23, -36, -32,
+    -33, -28, -22, -43,  -5, -32, -20, -41,
+];
+
+#[rustfmt::skip]
+const MG_KING: [i32; 64] = [
+    -65,  23,  16, -15, -56, -34,   2,  13,
+     29,  -1, -20,  -7,  -8,  -4, -38, -29,
+     -9,  24,   2, -16, -20,   6,  22, -22,
+    -17, -20, -12, -27, -30, -25, -14, -36,
+    -49,  -1, -27, -39, -46, -44, -33, -51,
+    -14, -14, -22, -46, -44, -30, -15, -27,
+      1,   7,  -8, -64, -43, -16,   9,   8,
+    -15,  36,  12, -54,   8, -28,  24,  14,
+];
+
+#[rustfmt::skip]
+const EG_KING: [i32; 64] = [
+    -74, -35, -18, -18, -11,  15,   4, -17,
+    -12,  17,  14,  17,  17,  38,  23,  11,
+     10,  17,  23,  15,  20,  45,  44,  13,
+     -8,  22,  24,  27,  26,  33,  26,   3,
+    -18,  -4,  21,  24,  27,  23,   9, -11,
+    -19,  -3,  11,  21,  23,  16,   7,  -9,
+    -27, -11,   4,  13,  14,   4,  -5, -17,
+    -53, -34, -21, -11, -28, -14, -24, -43,
+];
+
+fn piece_square_tables(piece: Pieces) -> (&'static [i32; 64], &'static [i32; 64]) {
+    return match piece {
+        Pieces::Pawns => (&MG_PAWN, &EG_PAWN),
+        Pieces::Knights => (&MG_KNIGHT, &EG_KNIGHT),
+        Pieces::Bishops => (&MG_BISHOP, &EG_BISHOP),
+        Pieces::Rooks => (&MG_ROOK, &EG_ROOK),
+        Pieces::Queens => (&MG_QUEEN, &EG_QUEEN),
+        Pieces::Kings => (&MG_KING, &EG_KING),
+    };
+}
+
+/// The square to look up in the tables: flipping the rank mirrors a8 onto a1.
+fn table_square(square: u32, white: bool) -> usize {
+    return if white {
+        square as usize
+    } else {
+        (square ^ 56) as usize
+    };
+}
+
+/// 24 with every piece still on the board down to 0 with only pawns and kings left.
+/// Promotions can push the count above the maximum, so it is capped.
+fn game_phase(board: &Board) -> i32 {
+    let phase: i32 = PIECES
+        .iter()
+        .map(|&piece| phase_weight(piece) * board.pieces[piece as usize].count_ones() as i32)
+        .sum();
+
+    return phase.min(MAX_PHASE);
+}
+
+fn material_and_piece_squares(board: &Board, trace: &mut EvalTrace) {
+    for piece in PIECES {
+        let (mg_table, eg_table) = piece_square_tables(piece);
+
+        for (white, own_pieces) in [(true, board.white_pieces), (false, board.black_pieces)] {
+            let mut pieces = board.pieces[piece as usize] & own_pieces;
+
+            while pieces > 0 {
+                let square = table_square(pieces.trailing_zeros(), white);
+                trace.add(Term::Material, white, piece_value(piece));
+                trace.add(
+                    Term::PieceSquare,
+                    white,
+                    Score::new(mg_table[square], eg_table[square]),
+                );
+
+                pieces &= pieces - 1;
+            }
+        }
+    }
+}
+
+fn build_trace(board: &Board, pawns: PawnEntry) -> EvalTrace {
+    let mut trace = EvalTrace {
+        phase: game_phase(board),
+        white_turn: board.game_state.white_turn,
+        ..EvalTrace::default()
+    };
+
+    material_and_piece_squares(board, &mut trace);
+    trace.add(Term::PawnStructure, true, pawns.scores[0]);
+    trace.add(Term::PawnStructure, false, pawns.scores[1]);
+
+    for white in [true, false] {
+        trace.add(Term::Mobility, white, mobility(board, white));
+        trace.add(Term::PieceActivity, white, activity(board, white));
+        trace.add(Term::KingSafety, white, king_safety(board, white));
+    }
+
+    return trace;
+}
+
+/// Evaluates the position term by term.
+pub fn trace(board: &Board) -> EvalTrace {
+    return build_trace(board, evaluate_pawns(board));
+}
+
+/// Static evaluation in centipawns from the side to move's point of view, blended
+/// from middlegame to endgame values by game phase.
+pub fn evaluate(board: &Board) -> i32 {
+    return trace(board).score();
+}
+
+/// `evaluate`, with the pawn structure looked up in `pawn_table` first.
+pub fn evaluate_cached(board: &Board, pawn_table: &mut PawnTable) -> i32 {
+    return build_trace(board, pawn_table.get(board)).score();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::START_FEN;
+
+    /// The same position with the colours swapped and the board flipped vertically.
+    fn mirror_fen(fen: &str) -> String {
+        let fields: Vec<&str> = fen.split(' ').collect();
+        let swap_case = |text: &str| -> String {
+            text.chars()
+                .map(|c| {
+                    if c.is_ascii_uppercase() {
+                        c.to_ascii_lowercase()
+                    } else {
+                        c.to_ascii_uppercase()
+                    }
+                })
+                .collect()
+        };
+
+        let ranks: Vec<String> = fields[0].split('/').rev().map(swap_case).collect();
+        let side = if fields[1] == "w" { "b" } else { "w" };
+        let en_passant = match fields[3] {
+            "-" => "-".to_string(),
+            square => {
+                let rank = 9 - square[1..].parse::<u32>().unwrap();
+                format!("{}{}", &square[..1], rank)
+            }
+        };
+
+        return format!(
+            "{} {} {} {} {} {}",
+            ranks.join("/"),
+            side,
+            swap_case(fields[2]),
+            en_passant,
+            fields[4],
+            fields[5]
+        );
+    }
+
+    fn evaluate_fen(fen: &str) -> i32 {
+        return evaluate(&Board::from_fen(fen).unwrap());
+    }
+
+    #[test]
+    fn start_position_is_equal() {
+        assert_eq!(0, evaluate_fen(START_FEN));
+        assert_eq!(MAX_PHASE, game_phase(&Board::from_fen(START_FEN).unwrap()));
+    }
+
+    #[test]
+    fn mirrored_positions_evaluate_the_same() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "4k3/8/8/8/8/8/4P3/4K3 b - - 0 1",
+        ] {
+            assert_eq!(evaluate_fen(fen), evaluate_fen(&mirror_fen(fen)), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn score_is_from_side_to_move() {
+        let white = evaluate_fen("4k3/8/8/8/8/8/8/Q3K3 w - - 0 1");
+        let black = evaluate_fen("4k3/8/8/8/8/8/8/Q3K3 b - - 0 1");
+
+        assert!(white > 800);
+        assert_eq!(white, -black);
+    }
+
+    #[test]
+    fn trace_adds_up() {
+        let board =
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R b KQkq - 0 1")
+                .unwrap();
+        let trace = trace(&board);
+
+        // Material is level, so only the piece-square tables decide
+        let material = trace.get(Term::Material, true) - trace.get(Term::Material, false);
+        assert_eq!(Score::default(), material);
+        assert_eq!(-trace.taper(trace.total()), evaluate(&board));
+
+        let table = trace.to_string();
+        for term in TERMS {
+            assert!(table.contains(term.name()));
+        }
+        assert!(table.contains(&format!(
+            "Evaluation (side to move): {} cp",
+            evaluate(&board)
+        )));
+    }
+
+    #[test]
+    fn tables_are_indexed_from_a8() {
+        // A pawn about to promote is worth far more than one on its starting square
+        let advanced = evaluate_fen("4k3/P7/8/8/8/8/8/4K3 w - - 0 1");
+        let home = evaluate_fen("4k3/8/8/8/8/8/P7/4K3 w - - 0 1");
+        assert!(advanced > home + 100);
+
+        let advanced = evaluate_fen("4k3/8/8/8/8/8/p7/4K3 b - - 0 1");
+        let home = evaluate_fen("4k3/p7/8/8/8/8/8/4K3 b - - 0 1");
+        assert!(advanced > home + 100);
+    }
+}
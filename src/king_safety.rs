@@ -0,0 +1,205 @@
+use crate::{
+    bitboard::Bitboard,
+    board::Board,
+    evaluation::Score,
+    move_generation::{king_attacks, piece_attacks},
+    pawn_structure::{file_mask, forward_ranks},
+    piece::Pieces,
+};
+
+/// For an own pawn one and two ranks in front of the king.
+const SHIELD: [Score; 2] = [Score::new(15, 0), Score::new(8, 0)];
+
+/// For the closest enemy pawn on a file next to the king, by how many ranks it is
+/// away. A pawn right in front of the king is blocked and does little.
+const STORM: [Score; 5] = [
+    Score::new(0, 0),
+    Score::new(-5, 0),
+    Score::new(-25, 0),
+    Score::new(-15, 0),
+    Score::new(-6, 0),
+];
+
+const OPEN_FILE: Score = Score::new(-25, 0);
+const HALF_OPEN_FILE: Score = Score::new(-12, 0);
+
+/// Attack units per king zone square hit by a piece of each kind.
+fn attack_weight(piece: Pieces) -> i32 {
+    return match piece {
+        Pieces::Knights | Pieces::Bishops => 2,
+        Pieces::Rooks => 3,
+        Pieces::Queens => 5,
+        Pieces::Pawns | Pieces::Kings => 0,
+    };
+}
+
+/// Percentage of the attack units that counts, by number of attacking pieces. A lone
+/// attacker can't mate, so it doesn't count at all.
+const ATTACKER_SCALE: [i32; 8] = [0, 0, 50, 75, 88, 94, 97, 99];
+
+/// Centipawns per attack unit.
+const ATTACK_UNIT: Score = Score::new(-8, -2);
+
+/// The squares around the king and one more rank towards the enemy.
+fn king_zone(king_square: u32, white: bool) -> Bitboard {
+    let zone = king_attacks(king_square) | 1 << king_square;
+
+    return zone | if white { zone >> 8 } else { zone << 8 };
+}
+
+/// How many ranks in front of the king the closest of `pawns` is.
+fn closest_distance(pawns: Bitboard, king_square: u32, white: bool) -> Option<u32> {
+    if pawns == 0 {
+        return None;
+    }
+
+    return Some(if white {
+        king_square / 8 - (63 - pawns.leading_zeros()) / 8
+    } else {
+        pawns.trailing_zeros() / 8 - king_square / 8
+    });
+}
+
+fn pawn_cover(board: &Board, king_square: u32, white: bool) -> Score {
+    let mut score = Score::default();
+
+    let pawns = board.pieces[Pieces::Pawns as usize];
+    let (own_pawns, enemy_pawns) = if white {
+        (pawns & board.white_pieces, pawns & board.black_pieces)
+    } else {
+        (pawns & board.black_pieces, pawns & board.white_pieces)
+    };
+    let forward = forward_ranks(king_square, white);
+
+    let king_file = king_square % 8;
+    for file in king_file.saturating_sub(1)..=(king_file + 1).min(7) {
+        // Any square on the file picks it
+        let file = file_mask(file);
+
+        if let Some(distance @ 1..=2) =
+            closest_distance(own_pawns & file & forward, king_square, white)
+        {
+            score += SHIELD[distance as usize - 1];
+        }
+
+        if let Some(distance @ 1..=4) =
+            closest_distance(enemy_pawns & file & forward, king_square, white)
+        {
+            score += STORM[distance as usize];
+        }
+
+        if pawns & file == 0 {
+            score += OPEN_FILE;
+        } else if own_pawns & file == 0 {
+            score += HALF_OPEN_FILE;
+        }
+    }
+
+    return score;
+}
+
+fn zone_attacks(board: &Board, king_square: u32, white: bool) -> Score {
+    let zone = king_zone(king_square, white);
+    let occupied = board.white_pieces | board.black_pieces;
+    let enemy_pieces = if white {
+        board.black_pieces
+    } else {
+        board.white_pieces
+    };
+
+    let mut attackers = 0;
+    let mut units = 0;
+
+    for piece in [
+        Pieces::Knights,
+        Pieces::Bishops,
+        Pieces::Rooks,
+        Pieces::Queens,
+    ] {
+        let mut pieces = board.pieces[piece as usize] & enemy_pieces;
+
+        while pieces > 0 {
+            let square = pieces.trailing_zeros();
+            let attacks = piece_attacks(piece, square, occupied) & zone;
+
+            if attacks > 0 {
+                attackers += 1;
+                units += attack_weight(piece) * attacks.count_ones() as i32;
+            }
+
+            pieces &= pieces - 1;
+        }
+    }
+
+    let units = units * ATTACKER_SCALE[attackers.min(7)] / 100;
+
+    return Score::new(ATTACK_UNIT.mg * units, ATTACK_UNIT.eg * units);
+}
+
+/// King safety of one side: its pawn shield, enemy pawns storming it, open files next
+/// to it and the enemy pieces bearing down on the squares around it.
+pub fn king_safety(board: &Board, white: bool) -> Score {
+    let own_pieces = if white {
+        board.white_pieces
+    } else {
+        board.black_pieces
+    };
+    let king = board.pieces[Pieces::Kings as usize] & own_pieces;
+    if king == 0 {
+        return Score::default();
+    }
+
+    let king_square = king.trailing_zeros();
+
+    return pawn_cover(board, king_square, white) + zone_attacks(board, king_square, white);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn white_safety(fen: &str) -> Score {
+        return king_safety(&Board::from_fen(fen).unwrap(), true);
+    }
+
+    #[test]
+    fn pawn_shield() {
+        let castled = white_safety("4k3/8/8/8/8/8/5PPP/6K1 w - - 0 1");
+        assert_eq!(SHIELD[0] + SHIELD[0] + SHIELD[0], castled);
+
+        let pushed = white_safety("4k3/8/8/8/8/6P1/5P1P/6K1 w - - 0 1");
+        assert_eq!(SHIELD[0] + SHIELD[1] + SHIELD[0], pushed);
+    }
+
+    #[test]
+    fn open_files_and_storms() {
+        let open = white_safety("4k3/8/8/8/8/8/5P1P/6K1 w - - 0 1");
+        assert_eq!(SHIELD[0] + SHIELD[0] + OPEN_FILE, open);
+
+        // The black g-pawn two ranks in front makes the g-file half-open and storms
+        let stormed = white_safety("4k3/8/8/8/8/6p1/5P1P/6K1 w - - 0 1");
+        assert_eq!(SHIELD[0] + SHIELD[0] + HALF_OPEN_FILE + STORM[2], stormed);
+    }
+
+    #[test]
+    fn attackers_on_the_king_zone() {
+        // The queen alone doesn't count, together with the knight it does
+        let queen = white_safety("4k3/8/8/8/8/5q2/5PPP/6K1 w - - 0 1");
+        let queen_and_knight = white_safety("4k3/8/8/8/4n3/5q2/5PPP/6K1 w - - 0 1");
+        let shield = SHIELD[0] + SHIELD[0] + SHIELD[0];
+
+        assert_eq!(shield, queen);
+        assert!(queen_and_knight.mg < shield.mg - 50);
+    }
+
+    #[test]
+    fn king_safety_is_symmetric() {
+        let white = white_safety("4k3/8/8/8/4n3/5q2/5PPP/6K1 w - - 0 1");
+        let black = king_safety(
+            &Board::from_fen("6k1/5ppp/5Q2/4N3/8/8/8/4K3 w - - 0 1").unwrap(),
+            false,
+        );
+
+        assert_eq!(white, black);
+    }
+}
@@ -0,0 +1,28 @@
+#![allow(clippy::needless_return)]
+// Constructors here build tables or starting positions, so they stay explicit `new`s,
+// and the transposition table's `len` counts slots, which are never empty
+#![allow(clippy::new_without_default, clippy::len_without_is_empty)]
+
+pub mod bench;
+pub mod bitboard;
+pub mod board;
+pub mod direction;
+pub mod evaluation;
+pub mod king_safety;
+pub mod magic;
+pub mod r#move;
+pub mod move_generation;
+pub mod move_picker;
+pub mod pawn_structure;
+pub mod perft;
+pub mod piece;
+pub mod piece_activity;
+pub mod search;
+pub mod see;
+pub mod smp;
+#[cfg(test)]
+mod test_util;
+pub mod time_manager;
+pub mod transposition_table;
+pub mod uci;
+pub mod zobrist;
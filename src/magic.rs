@@ -0,0 +1,294 @@
+use std::sync::OnceLock;
+
+use crate::{
+    bitboard::{Bitboard, FILE_A, FILE_H, RANK1, RANK8},
+    move_generation::{bishop_ray_attacks, rook_ray_attacks},
+};
+
+const ROOK_MAGICS: [Bitboard; 64] = [
+    0x0080001020400080,
+    0x0040001000200040,
+    0x0080081000200080,
+    0x0080040800100080,
+    0x0080020400080080,
+    0x0080010200040080,
+    0x0080008001000200,
+    0x0080002040800100,
+    0x0000800020400080,
+    0x0000400020005000,
+    0x0000801000200080,
+    0x0000800800100080,
+    0x0000800400080080,
+    0x0000800200040080,
+    0x0000800100020080,
+    0x0000800040800100,
+    0x0000208000400080,
+    0x0000404000201000,
+    0x0000808010002000,
+    0x0000808008001000,
+    0x0000808004000800,
+    0x0000808002000400,
+    0x0000010100020004,
+    0x0000020000408104,
+    0x0000208080004000,
+    0x0000200040005000,
+    0x0000100080200080,
+    0x0000080080100080,
+    0x0000040080080080,
+    0x0000020080040080,
+    0x0000010080800200,
+    0x0000800080004100,
+    0x0000204000800080,
+    0x0000200040401000,
+    0x0000100080802000,
+    0x0000080080801000,
+    0x0000040080800800,
+    0x0000020080800400,
+    0x0000020001010004,
+    0x0000800040800100,
+    0x0000204000808000,
+    0x0000200040008080,
+    0x0000100020008080,
+    0x0000080010008080,
+    0x0000040008008080,
+    0x0000020004008080,
+    0x0000010002008080,
+    0x0000004081020004,
+    0x0000204000800080,
+    0x0000200040008080,
+    0x0000100020008080,
+    0x0000080010008080,
+    0x0000040008008080,
+    0x0000020004008080,
+    0x0000800100020080,
+    0x0000800041000080,
+    0x00FFFCDDFCED714A,
+    0x007FFCDDFCED714A,
+    0x003FFFCDFFD88096,
+    0x0000040810002101,
+    0x0001000204080011,
+    0x0001000204000801,
+    0x0001000082000401,
+    0x0001FFFAABFAD1A2,
+];
+
+const BISHOP_MAGICS: [Bitboard; 64] = [
+    0x2240081A22902100,
+    0x8020055224950082,
+    0x20100C04A7220384,
+    0x004820A020000400,
+    0x0E14052000008006,
+    0x0005140240000002,
+    0x00A0420805400800,
+    0x0202021042021000,
+    0xA0580488B0142080,
+    0x8102024404043040,
+    0x8180086204002004,
+    0x4220181481040202,
+    0x8000420210000000,
+    0x80002088A0080404,
+    0x0000084808241200,
+    0x040004422A100200,
+    0x0044041010104140,
+    0x1021280222040100,
+    0x00480040820010A2,
+    0x0088000082004011,
+    0x8084000200944000,
+    0x0441A00A00842050,
+    0x0401100C00821028,
+    0x0040210304022E40,
+    0x0004200110321042,
+    0x104A300408010818,
+    0x0000280810004044,
+    0x0008080000820002,
+    0x0115004094044001,
+    0x2941090012100091,
+    0x0841084202021004,
+    0x00020048008400BA,
+    0x100802B0000A2024,
+    0x000402680C200100,
+    0x4000109005280840,
+    0x0001020080880080,
+    0x0448020400001100,
+    0x0004180020021000,
+    0x0010016100004400,
+    0x0040911200004A10,
+    0x1802011040000808,
+    0x4021080230C90210,
+    0x0944101088001000,
+    0xC000082018000108,
+    0x800420220C000081,
+    0x0804408801100200,
+    0x4802080A0C110080,
+    0x2201440102000040,
+    0x1041080110488404,
+    0x1010248608210001,
+    0x030012020F044148,
+    0x0000001F04090082,
+    0x0000000410440400,
+    0x20000490224A0000,
+    0x021020010402B844,
+    0x8004012401020000,
+    0x1000288200A02004,
+    0x0010A444041C1302,
+    0x000000004210900C,
+    0x1106202240208820,
+    0x0080200110020880,
+    0x0008080820080082,
+    0x0800A00801082881,
+    0x8020940408182820,
+];
+
+#[derive(Clone, Copy, Default)]
+struct Magic {
+    mask: Bitboard,
+    magic: Bitboard,
+    shift: u32,
+    offset: usize,
+}
+
+/// Attack lookup for one kind of slider. Every square owns a slice of `attacks`,
+/// indexed by multiplying the relevant blockers with the square's magic number.
+pub struct MagicTable {
+    magics: [Magic; 64],
+    attacks: Vec<Bitboard>,
+}
+
+impl MagicTable {
+    fn new(
+        magic_numbers: &[Bitboard; 64],
+        mask: fn(u32) -> Bitboard,
+        slow_attacks: fn(u32, Bitboard) -> Bitboard,
+    ) -> Self {
+        let mut table = MagicTable {
+            magics: [Magic::default(); 64],
+            attacks: Vec::new(),
+        };
+
+        for square in 0..64 {
+            let mask = mask(square);
+            let magic = Magic {
+                mask,
+                magic: magic_numbers[square as usize],
+                shift: 64 - mask.count_ones(),
+                offset: table.attacks.len(),
+            };
+
+            table.magics[square as usize] = magic;
+            table
+                .attacks
+                .resize(magic.offset + (1 << mask.count_ones()), 0);
+
+            // Walk every subset of the mask with the carry-rippler trick
+            let mut blockers: Bitboard = 0;
+            loop {
+                let index = table.index(square, blockers);
+                table.attacks[index] = slow_attacks(square, blockers);
+
+                blockers = blockers.wrapping_sub(mask) & mask;
+                if blockers == 0 {
+                    break;
+                }
+            }
+        }
+
+        return table;
+    }
+
+    fn index(&self, square: u32, occupied: Bitboard) -> usize {
+        let magic = &self.magics[square as usize];
+        let blockers = occupied & magic.mask;
+
+        return magic.offset + (blockers.wrapping_mul(magic.magic) >> magic.shift) as usize;
+    }
+
+    pub fn attacks(&self, square: u32, occupied: Bitboard) -> Bitboard {
+        return self.attacks[self.index(square, occupied)];
+    }
+}
+
+/// Squares whose occupancy changes a rook's attacks. The edge at the end of a ray
+/// never blocks anything beyond it, so it is left out.
+fn rook_mask(square: u32) -> Bitboard {
+    let rank_mask = RANK8 << (8 * (square / 8));
+    let file_mask = FILE_A << (square % 8);
+
+    let edges = ((RANK1 | RANK8) & !rank_mask) | ((FILE_A | FILE_H) & !file_mask);
+
+    return (rank_mask ^ file_mask) & !edges;
+}
+
+fn bishop_mask(square: u32) -> Bitboard {
+    return bishop_ray_attacks(square, 0) & !(RANK1 | RANK8 | FILE_A | FILE_H);
+}
+
+static ROOK_TABLE: OnceLock<MagicTable> = OnceLock::new();
+static BISHOP_TABLE: OnceLock<MagicTable> = OnceLock::new();
+
+pub fn rook_attacks(square: u32, occupied: Bitboard) -> Bitboard {
+    return ROOK_TABLE
+        .get_or_init(|| MagicTable::new(&ROOK_MAGICS, rook_mask, rook_ray_attacks))
+        .attacks(square, occupied);
+}
+
+pub fn bishop_attacks(square: u32, occupied: Bitboard) -> Bitboard {
+    return BISHOP_TABLE
+        .get_or_init(|| MagicTable::new(&BISHOP_MAGICS, bishop_mask, bishop_ray_attacks))
+        .attacks(square, occupied);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::next_random;
+
+    fn cross_check(
+        mask: fn(u32) -> Bitboard,
+        magic_attacks: fn(u32, Bitboard) -> Bitboard,
+        ray_attacks: fn(u32, Bitboard) -> Bitboard,
+    ) {
+        let mut random_state: u64 = 0x9E3779B97F4A7C15;
+
+        for square in 0..64 {
+            let mask = mask(square);
+            let mut blockers: Bitboard = 0;
+
+            loop {
+                let noise = next_random(&mut random_state) & !mask & !(1 << square);
+
+                for occupied in [blockers, blockers | noise] {
+                    assert_eq!(
+                        ray_attacks(square, occupied),
+                        magic_attacks(square, occupied),
+                        "square {square}, occupancy {occupied:064b}"
+                    );
+                }
+
+                blockers = blockers.wrapping_sub(mask) & mask;
+                if blockers == 0 {
+                    break;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rook_magics_match_rays() {
+        cross_check(rook_mask, rook_attacks, rook_ray_attacks);
+    }
+
+    #[test]
+    fn bishop_magics_match_rays() {
+        cross_check(bishop_mask, bishop_attacks, bishop_ray_attacks);
+    }
+
+    #[test]
+    fn masks_leave_out_edges() {
+        // a8 rook: b8..g8 and a7..a2
+        assert_eq!(0x000101010101017E, rook_mask(0));
+        // e4 rook: b4..g4 and e7..e2 without e4 itself
+        assert_eq!(rook_mask(36).count_ones(), 10);
+        assert_eq!(bishop_mask(0).count_ones(), 6);
+        assert_eq!(bishop_mask(27).count_ones(), 9);
+    }
+}
@@ -1,8 +1,54 @@
-use crate::piece::Pieces;
+use crate::{board::num_to_square, piece::Pieces};
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct Move {
     pub from: u32,
     pub to: u32,
     pub piece: Pieces,
-}
\ No newline at end of file
+    pub promotion: Option<Pieces>,
+}
+
+/// Long algebraic notation as used by UCI, e.g. `e2e4` or `a7a8q`.
+impl std::fmt::Display for Move {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        let promotion = match self.promotion {
+            Some(Pieces::Queens) => "q",
+            Some(Pieces::Rooks) => "r",
+            Some(Pieces::Bishops) => "b",
+            Some(Pieces::Knights) => "n",
+            _ => "",
+        };
+
+        write!(
+            f,
+            "{}{}{}",
+            num_to_square(self.from),
+            num_to_square(self.to),
+            promotion
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn move_display() {
+        let r#move = Move {
+            from: 52,
+            to: 36,
+            piece: Pieces::Pawns,
+            promotion: None,
+        };
+        assert_eq!("e2e4", format!("{}", r#move));
+
+        let r#move = Move {
+            from: 9,
+            to: 0,
+            piece: Pieces::Pawns,
+            promotion: Some(Pieces::Knights),
+        };
+        assert_eq!("b7a8n", format!("{}", r#move));
+    }
+}
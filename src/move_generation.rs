@@ -1,12 +1,50 @@
 use crate::{
-    bitboard::{self, Bitboard, FILE_A, FILE_B, FILE_G, FILE_H, RANK4, RANK5},
-    board::{self, Board},
+    bitboard::{Bitboard, FILE_A, FILE_B, FILE_G, FILE_H, RANK1, RANK4, RANK5, RANK8},
+    board::Board,
     direction::Direction,
+    magic::{bishop_attacks, rook_attacks},
     piece::Pieces,
     r#move::Move,
 };
 
-fn pawn_moves_single_push(board: &Board, white_turn: bool) -> Vec<Move> {
+const PROMOTION_PIECES: [Pieces; 4] = [
+    Pieces::Queens,
+    Pieces::Rooks,
+    Pieces::Bishops,
+    Pieces::Knights,
+];
+
+/// Which moves the generators produce. `Captures` is for quiescence search: captures,
+/// en passant and promotions only. `Quiets` is everything else.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GenerationMode {
+    All,
+    Captures,
+    Quiets,
+}
+
+/// Adds a pawn move, expanded into every promotion when it reaches the last rank.
+fn push_pawn_move(moves: &mut Vec<Move>, from: u32, to: u32) {
+    if (1 << to) & (RANK1 | RANK8) > 0 {
+        for promotion in PROMOTION_PIECES {
+            moves.push(Move {
+                from,
+                to,
+                piece: Pieces::Pawns,
+                promotion: Some(promotion),
+            });
+        }
+    } else {
+        moves.push(Move {
+            from,
+            to,
+            piece: Pieces::Pawns,
+            promotion: None,
+        });
+    }
+}
+
+fn pawn_moves_single_push(board: &Board, white_turn: bool, mode: GenerationMode) -> Vec<Move> {
     let mut moves: Vec<Move> = Vec::new();
     let mut pawns = board.pieces[Pieces::Pawns as usize]
         & if white_turn {
@@ -16,23 +54,25 @@ fn pawn_moves_single_push(board: &Board, white_turn: bool) -> Vec<Move> {
         };
 
     let empty_tiles = !(board.white_pieces ^ board.black_pieces);
+    // Pushes onto the last rank are promotions, which count as captures here
+    let targets = match mode {
+        GenerationMode::All => empty_tiles,
+        GenerationMode::Captures => empty_tiles & (RANK1 | RANK8),
+        GenerationMode::Quiets => empty_tiles & !(RANK1 | RANK8),
+    };
 
     while pawns > 0 {
         let from: u32 = pawns.trailing_zeros();
 
         let pawn_move = if white_turn {
-            1 << from >> 8 & empty_tiles
+            1 << from >> 8 & targets
         } else {
-            1 << from << 8 & empty_tiles
+            1 << from << 8 & targets
         };
 
         if pawn_move > 0 {
             let to = pawn_move.trailing_zeros();
-            moves.push(Move {
-                from,
-                to,
-                piece: Pieces::Pawns,
-            });
+            push_pawn_move(&mut moves, from, to);
         }
 
         pawns &= !(1 << from);
@@ -42,16 +82,16 @@ fn pawn_moves_single_push(board: &Board, white_turn: bool) -> Vec<Move> {
 }
 
 fn pawn_moves_double_push(board: &Board, white_turn: bool) -> Vec<Move> {
-    let moves_one_push: Vec<Move> = pawn_moves_single_push(&board, white_turn);
+    let moves_one_push: Vec<Move> = pawn_moves_single_push(board, white_turn, GenerationMode::All);
     let mut moves: Vec<Move> = Vec::new();
 
     let empty_tiles = !(board.white_pieces ^ board.black_pieces);
 
     for one_push in moves_one_push {
         let pawn_move = if white_turn {
-            0 | (1 << one_push.to) >> 8 & empty_tiles & RANK4
+            (1 << one_push.to) >> 8 & empty_tiles & RANK4
         } else {
-            0 | (1 << one_push.to) << 8 & empty_tiles & RANK5
+            (1 << one_push.to) << 8 & empty_tiles & RANK5
         };
 
         if pawn_move > 0 {
@@ -61,6 +101,7 @@ fn pawn_moves_double_push(board: &Board, white_turn: bool) -> Vec<Move> {
                 from,
                 to,
                 piece: Pieces::Pawns,
+                promotion: None,
             });
         }
     }
@@ -103,11 +144,7 @@ fn attacking_pawn_moves(board: &Board) -> Vec<Move> {
 
         while pawn_moves > 0 {
             let to = pawn_moves.trailing_zeros();
-            moves.push(Move {
-                from,
-                to,
-                piece: Pieces::Pawns,
-            });
+            push_pawn_move(&mut moves, from, to);
 
             pawn_moves &= !(1 << to);
         }
@@ -118,7 +155,19 @@ fn attacking_pawn_moves(board: &Board) -> Vec<Move> {
     return moves;
 }
 
-fn knight_moves(board: &Board, white_turn: bool) -> Vec<Move> {
+/// Squares a piece may move to: anything but its own pieces, only enemy pieces when
+/// generating captures and only empty squares when generating quiet moves.
+fn move_targets(board: &Board, own_pieces: Bitboard, mode: GenerationMode) -> Bitboard {
+    let occupied = board.white_pieces | board.black_pieces;
+
+    return match mode {
+        GenerationMode::All => !own_pieces,
+        GenerationMode::Captures => occupied & !own_pieces,
+        GenerationMode::Quiets => !occupied,
+    };
+}
+
+fn knight_moves(board: &Board, white_turn: bool, mode: GenerationMode) -> Vec<Move> {
     let mut moves: Vec<Move> = Vec::new();
     let mut knights = board.pieces[Pieces::Knights as usize]
         & if white_turn {
@@ -132,88 +181,97 @@ fn knight_moves(board: &Board, white_turn: bool) -> Vec<Move> {
     } else {
         board.black_pieces
     };
+    let targets = move_targets(board, own_pieces, mode);
 
     while knights > 0 {
         let from: u32 = knights.trailing_zeros();
         let knight_position_bitboard = 1 << from;
 
-        let knight_move = (knight_position_bitboard << 17) & (!FILE_A & !own_pieces);
+        let knight_move = (knight_position_bitboard << 17) & (!FILE_A & targets);
         if knight_move > 0 {
             let to = knight_move.trailing_zeros();
             moves.push(Move {
                 from,
                 to,
                 piece: Pieces::Knights,
+                promotion: None,
             });
         }
 
-        let knight_move = ((knight_position_bitboard << 10) & (!FILE_A & !FILE_B)) & !own_pieces;
+        let knight_move = ((knight_position_bitboard << 10) & (!FILE_A & !FILE_B)) & targets;
         if knight_move > 0 {
             let to = knight_move.trailing_zeros();
             moves.push(Move {
                 from,
                 to,
                 piece: Pieces::Knights,
+                promotion: None,
             });
         }
 
-        let knight_move = ((knight_position_bitboard >> 6) & (!FILE_A & !FILE_B)) & !own_pieces;
+        let knight_move = ((knight_position_bitboard >> 6) & (!FILE_A & !FILE_B)) & targets;
         if knight_move > 0 {
             let to = knight_move.trailing_zeros();
             moves.push(Move {
                 from,
                 to,
                 piece: Pieces::Knights,
+                promotion: None,
             });
         }
 
-        let knight_move = ((knight_position_bitboard >> 15) & (!FILE_A)) & !own_pieces;
+        let knight_move = ((knight_position_bitboard >> 15) & (!FILE_A)) & targets;
         if knight_move > 0 {
             let to = knight_move.trailing_zeros();
             moves.push(Move {
                 from,
                 to,
                 piece: Pieces::Knights,
+                promotion: None,
             });
         }
 
-        let knight_move = ((knight_position_bitboard << 15) & (!FILE_H)) & !own_pieces;
+        let knight_move = ((knight_position_bitboard << 15) & (!FILE_H)) & targets;
         if knight_move > 0 {
             let to = knight_move.trailing_zeros();
             moves.push(Move {
                 from,
                 to,
                 piece: Pieces::Knights,
+                promotion: None,
             });
         }
 
-        let knight_move = ((knight_position_bitboard << 6) & (!FILE_G & !FILE_H)) & !own_pieces;
+        let knight_move = ((knight_position_bitboard << 6) & (!FILE_G & !FILE_H)) & targets;
         if knight_move > 0 {
             let to = knight_move.trailing_zeros();
             moves.push(Move {
                 from,
                 to,
                 piece: Pieces::Knights,
+                promotion: None,
             });
         }
 
-        let knight_move = ((knight_position_bitboard >> 10) & (!FILE_G & !FILE_H)) & !own_pieces;
+        let knight_move = ((knight_position_bitboard >> 10) & (!FILE_G & !FILE_H)) & targets;
         if knight_move > 0 {
             let to = knight_move.trailing_zeros();
             moves.push(Move {
                 from,
                 to,
                 piece: Pieces::Knights,
+                promotion: None,
             });
         }
 
-        let knight_move = ((knight_position_bitboard >> 17) & (!FILE_H)) & !own_pieces;
+        let knight_move = ((knight_position_bitboard >> 17) & (!FILE_H)) & targets;
         if knight_move > 0 {
             let to = knight_move.trailing_zeros();
             moves.push(Move {
                 from,
                 to,
                 piece: Pieces::Knights,
+                promotion: None,
             });
         }
 
@@ -223,6 +281,7 @@ fn knight_moves(board: &Board, white_turn: bool) -> Vec<Move> {
     return moves;
 }
 
+#[cfg(test)]
 const COMPASS_DIRECTIONS: [(i32, i32); 8] = [
     (0, -1),
     (1, -1),
@@ -234,17 +293,18 @@ const COMPASS_DIRECTIONS: [(i32, i32); 8] = [
     (-1, -1),
 ];
 
+#[cfg(test)]
 fn create_attack_rays() -> [[Bitboard; 8]; 64] {
     let mut attack_rays = [[0; 8]; 64];
 
-    for square in 0..64 {
+    for (square, attack_ray) in attack_rays.iter_mut().enumerate() {
         let mut rays = [0; 8];
 
         for (dir, &(dx, dy)) in COMPASS_DIRECTIONS.iter().enumerate() {
             let mut x = (square % 8) as i32;
             let mut y = (square / 8) as i32;
 
-            while x >= 0 && x < 8 && y >= 0 && y < 8 {
+            while (0..8).contains(&x) && (0..8).contains(&y) {
                 let target_square = y * 8 + x;
                 rays[dir] |= 1u64 << target_square;
 
@@ -254,13 +314,13 @@ fn create_attack_rays() -> [[Bitboard; 8]; 64] {
             rays[dir] &= !(1 << square);
         }
 
-        attack_rays[square] = rays;
+        *attack_ray = rays;
     }
 
     return attack_rays;
 }
 
-const ATTACK_RAYS: [[Bitboard; 8]; 64] = [
+pub const ATTACK_RAYS: [[Bitboard; 8]; 64] = [
     [0, 0, 254, 9241421688590303744, 72340172838076672, 0, 0, 0],
     [0, 0, 252, 36099303471055872, 144680345676153344, 256, 1, 0],
     [0, 0, 248, 141012904183808, 289360691352306688, 66048, 3, 0],
@@ -849,76 +909,34 @@ const ATTACK_RAYS: [[Bitboard; 8]; 64] = [
     ],
 ];
 
-fn rook_moves(board: &Board, white_turn: bool) -> Vec<Move> {
+fn rook_moves(board: &Board, white_turn: bool, mode: GenerationMode) -> Vec<Move> {
     let mut moves: Vec<Move> = Vec::new();
 
-    let mut rooks = if white_turn {
-        board.pieces[Pieces::Rooks as usize] & board.white_pieces
+    let own_pieces = if white_turn {
+        board.white_pieces
     } else {
-        board.pieces[Pieces::Rooks as usize] & board.black_pieces
+        board.black_pieces
     };
+    let targets = move_targets(board, own_pieces, mode);
 
+    let mut rooks = board.pieces[Pieces::Rooks as usize] & own_pieces;
     let all_pieces = board.black_pieces | board.white_pieces;
 
     while rooks > 0 {
         let from = rooks.trailing_zeros();
-        let directions = [
-            Direction::North,
-            Direction::East,
-            Direction::South,
-            Direction::West,
-        ];
+        let mut rook_moves = piece_attacks(Pieces::Rooks, from, all_pieces) & targets;
 
-        for direction in directions {
-            let attack_ray = ATTACK_RAYS[from as usize][direction];
-            // println!("{:064b}", attack_ray);
-            let blockers = attack_ray & all_pieces;
-            // println!("{:064b}", blockers);
-            let mut rook_moves;
-
-            if blockers > 0 {
-                // println!("{:064b}\n", ATTACK_RAYS[blockers.trailing_zeros() as usize][direction]);
-                rook_moves = match direction {
-                    Direction::North => {
-                        attack_ray ^ ATTACK_RAYS[63 - blockers.leading_zeros() as usize][direction]
-                    }
-                    Direction::East => {
-                        attack_ray ^ ATTACK_RAYS[blockers.trailing_zeros() as usize][direction]
-                    }
-                    Direction::South => {
-                        attack_ray ^ ATTACK_RAYS[blockers.trailing_zeros() as usize][direction]
-                    }
-                    Direction::West => {
-                        attack_ray ^ ATTACK_RAYS[63 - blockers.leading_zeros() as usize][direction]
-                    }
-                    _ => 0,
-                };
-            } else {
-                rook_moves = attack_ray;
-            }
-
-            rook_moves &= if white_turn {
-                !board.white_pieces
-            } else {
-                !board.black_pieces
-            };
-
-            while rook_moves > 0 {
-                let to = rook_moves.trailing_zeros();
-                moves.push(Move {
-                    from,
-                    to,
-                    piece: Pieces::Rooks,
-                });
-
-                rook_moves &= !(1 << to)
-            }
+        while rook_moves > 0 {
+            let to = rook_moves.trailing_zeros();
+            moves.push(Move {
+                from,
+                to,
+                piece: Pieces::Rooks,
+                promotion: None,
+            });
 
-            // println!("{:064b}\n", moves);
+            rook_moves &= !(1 << to)
         }
-        // let rook_attack_ray = ROOK_ATTACK_RAYS[from as usize];
-        // let blockers = rook_attack_ray & all_pieces;
-        // let blocker_ray = println!("\n{:064b}", rooks & !(1 << from));
 
         rooks &= !(1 << from)
     }
@@ -926,67 +944,33 @@ fn rook_moves(board: &Board, white_turn: bool) -> Vec<Move> {
     return moves;
 }
 
-fn bishop_moves(board: &Board, white_turn: bool) -> Vec<Move> {
+fn bishop_moves(board: &Board, white_turn: bool, mode: GenerationMode) -> Vec<Move> {
     let mut moves: Vec<Move> = Vec::new();
 
-    let mut bishops = if white_turn {
-        board.pieces[Pieces::Bishops as usize] & board.white_pieces
+    let own_pieces = if white_turn {
+        board.white_pieces
     } else {
-        board.pieces[Pieces::Bishops as usize] & board.black_pieces
+        board.black_pieces
     };
+    let targets = move_targets(board, own_pieces, mode);
 
+    let mut bishops = board.pieces[Pieces::Bishops as usize] & own_pieces;
     let all_pieces = board.black_pieces | board.white_pieces;
 
     while bishops > 0 {
         let from = bishops.trailing_zeros();
-        let directions = [
-            Direction::NorthEast,
-            Direction::SouthEast,
-            Direction::SouthWest,
-            Direction::NorthWest,
-        ];
-
-        for direction in directions {
-            let attack_ray = ATTACK_RAYS[from as usize][direction];
-            let blockers = attack_ray & all_pieces;
-            let mut bishop_moves;
-
-            if blockers > 0 {
-                bishop_moves = match direction {
-                    Direction::NorthEast => {
-                        attack_ray ^ ATTACK_RAYS[63 - blockers.leading_zeros() as usize][direction]
-                    }
-                    Direction::SouthEast => {
-                        attack_ray ^ ATTACK_RAYS[blockers.trailing_zeros() as usize][direction]
-                    }
-                    Direction::SouthWest => {
-                        attack_ray ^ ATTACK_RAYS[blockers.trailing_zeros() as usize][direction]
-                    }
-                    Direction::NorthWest => {
-                        attack_ray ^ ATTACK_RAYS[63 - blockers.leading_zeros() as usize][direction]
-                    }
-                    _ => 0,
-                };
-            } else {
-                bishop_moves = attack_ray;
-            }
+        let mut bishop_moves = piece_attacks(Pieces::Bishops, from, all_pieces) & targets;
 
-            bishop_moves &= if white_turn {
-                !board.white_pieces
-            } else {
-                !board.black_pieces
-            };
-
-            while bishop_moves > 0 {
-                let to = bishop_moves.trailing_zeros();
-                moves.push(Move {
-                    from,
-                    to,
-                    piece: Pieces::Bishops,
-                });
+        while bishop_moves > 0 {
+            let to = bishop_moves.trailing_zeros();
+            moves.push(Move {
+                from,
+                to,
+                piece: Pieces::Bishops,
+                promotion: None,
+            });
 
-                bishop_moves &= !(1 << to)
-            }
+            bishop_moves &= !(1 << to)
         }
 
         bishops &= !(1 << from)
@@ -995,132 +979,590 @@ fn bishop_moves(board: &Board, white_turn: bool) -> Vec<Move> {
     return moves;
 }
 
-fn queen_moves(board: &Board, white_turn: bool) -> Vec<Move> {
+fn queen_moves(board: &Board, white_turn: bool, mode: GenerationMode) -> Vec<Move> {
     let mut moves: Vec<Move> = Vec::new();
 
-    let mut queens = if white_turn {
-        board.pieces[Pieces::Queens as usize] & board.white_pieces
+    let own_pieces = if white_turn {
+        board.white_pieces
     } else {
-        board.pieces[Pieces::Queens as usize] & board.black_pieces
+        board.black_pieces
     };
+    let targets = move_targets(board, own_pieces, mode);
 
+    let mut queens = board.pieces[Pieces::Queens as usize] & own_pieces;
     let all_pieces = board.black_pieces | board.white_pieces;
 
     while queens > 0 {
         let from = queens.trailing_zeros();
-        let directions = [
-            Direction::North,
-            Direction::NorthEast,
-            Direction::East,
-            Direction::SouthEast,
-            Direction::South,
-            Direction::SouthWest,
-            Direction::West,
-            Direction::NorthWest,
-        ];
+        let mut queen_moves = piece_attacks(Pieces::Queens, from, all_pieces) & targets;
 
-        for direction in directions {
-            let attack_ray = ATTACK_RAYS[from as usize][direction];
-            let blockers = attack_ray & all_pieces;
-            let mut queen_moves;
-
-            if blockers > 0 {
-                queen_moves = match direction {
-                    Direction::North => {
-                        attack_ray ^ ATTACK_RAYS[63 - blockers.leading_zeros() as usize][direction]
-                    }
-                    Direction::NorthEast => {
-                        attack_ray ^ ATTACK_RAYS[63 - blockers.leading_zeros() as usize][direction]
-                    }
-                    Direction::East => {
-                        attack_ray ^ ATTACK_RAYS[blockers.trailing_zeros() as usize][direction]
-                    }
-                    Direction::SouthEast => {
-                        attack_ray ^ ATTACK_RAYS[blockers.trailing_zeros() as usize][direction]
-                    }
-                    Direction::South => {
-                        attack_ray ^ ATTACK_RAYS[blockers.trailing_zeros() as usize][direction]
-                    }
-                    Direction::SouthWest => {
-                        attack_ray ^ ATTACK_RAYS[blockers.trailing_zeros() as usize][direction]
-                    }
-                    Direction::West => {
-                        attack_ray ^ ATTACK_RAYS[63 - blockers.leading_zeros() as usize][direction]
-                    }
-                    Direction::NorthWest => {
-                        attack_ray ^ ATTACK_RAYS[63 - blockers.leading_zeros() as usize][direction]
-                    }
-                    _ => 0,
-                };
-            } else {
-                queen_moves = attack_ray;
-            }
+        while queen_moves > 0 {
+            let to = queen_moves.trailing_zeros();
+            moves.push(Move {
+                from,
+                to,
+                piece: Pieces::Queens,
+                promotion: None,
+            });
 
-            queen_moves &= if white_turn {
-                !board.white_pieces
-            } else {
-                !board.black_pieces
-            };
+            queen_moves &= !(1 << to)
+        }
 
-            while queen_moves > 0 {
-                let to = queen_moves.trailing_zeros();
-                moves.push(Move {
-                    from,
-                    to,
-                    piece: Pieces::Queens,
-                });
+        queens &= !(1 << from)
+    }
 
-                queen_moves &= !(1 << to)
-            }
+    return moves;
+}
+
+pub fn ray_attacks(square: u32, occupied: Bitboard, direction: Direction) -> Bitboard {
+    let attack_ray = ATTACK_RAYS[square as usize][direction];
+    let blockers = attack_ray & occupied;
+
+    if blockers == 0 {
+        return attack_ray;
+    }
+
+    let first_blocker = match direction {
+        Direction::North | Direction::NorthEast | Direction::West | Direction::NorthWest => {
+            63 - blockers.leading_zeros()
         }
+        Direction::East | Direction::SouthEast | Direction::South | Direction::SouthWest => {
+            blockers.trailing_zeros()
+        }
+    };
 
-        queens &= !(1 << from)
+    return attack_ray ^ ATTACK_RAYS[first_blocker as usize][direction];
+}
+
+/// Reference implementation walking `ATTACK_RAYS`, used to fill the magic tables.
+pub fn rook_ray_attacks(square: u32, occupied: Bitboard) -> Bitboard {
+    return ray_attacks(square, occupied, Direction::North)
+        | ray_attacks(square, occupied, Direction::East)
+        | ray_attacks(square, occupied, Direction::South)
+        | ray_attacks(square, occupied, Direction::West);
+}
+
+pub fn bishop_ray_attacks(square: u32, occupied: Bitboard) -> Bitboard {
+    return ray_attacks(square, occupied, Direction::NorthEast)
+        | ray_attacks(square, occupied, Direction::SouthEast)
+        | ray_attacks(square, occupied, Direction::SouthWest)
+        | ray_attacks(square, occupied, Direction::NorthWest);
+}
+
+pub fn knight_attacks(square: u32) -> Bitboard {
+    let knight: Bitboard = 1 << square;
+
+    return ((knight << 17 | knight >> 15) & !FILE_A)
+        | ((knight << 10 | knight >> 6) & !FILE_A & !FILE_B)
+        | ((knight << 15 | knight >> 17) & !FILE_H)
+        | ((knight << 6 | knight >> 10) & !FILE_G & !FILE_H);
+}
+
+pub fn king_attacks(square: u32) -> Bitboard {
+    let king: Bitboard = 1 << square;
+
+    return (king >> 8 | king << 8)
+        | ((king >> 7 | king << 1 | king << 9) & !FILE_A)
+        | ((king >> 9 | king >> 1 | king << 7) & !FILE_H);
+}
+
+/// Squares attacked by a piece on `square`, sliders stopping at the first piece in
+/// `occupied`. Own pieces are included, so this is also what defends them. Pawns
+/// attack depending on their colour and go through `pawn_attacks` instead.
+pub fn piece_attacks(piece: Pieces, square: u32, occupied: Bitboard) -> Bitboard {
+    return match piece {
+        Pieces::Knights => knight_attacks(square),
+        Pieces::Bishops => bishop_attacks(square, occupied),
+        Pieces::Rooks => rook_attacks(square, occupied),
+        Pieces::Queens => bishop_attacks(square, occupied) | rook_attacks(square, occupied),
+        Pieces::Kings => king_attacks(square),
+        Pieces::Pawns => unreachable!("pawn attacks depend on their colour"),
+    };
+}
+
+/// Squares attacked by a pawn of the given colour standing on `square`.
+fn pawn_attacks(square: u32, white: bool) -> Bitboard {
+    let pawn: Bitboard = 1 << square;
+
+    return if white {
+        (pawn >> 7 & !FILE_A) | (pawn >> 9 & !FILE_H)
+    } else {
+        (pawn << 7 & !FILE_H) | (pawn << 9 & !FILE_A)
+    };
+}
+
+/// Every piece of either colour attacking `square`, with sliders blocked by `occupied`.
+pub fn attackers_to(board: &Board, square: u32, occupied: Bitboard) -> Bitboard {
+    let pawns = board.pieces[Pieces::Pawns as usize];
+    let queens = board.pieces[Pieces::Queens as usize];
+    let rooks_and_queens = board.pieces[Pieces::Rooks as usize] | queens;
+    let bishops_and_queens = board.pieces[Pieces::Bishops as usize] | queens;
+
+    return (pawn_attacks(square, false) & pawns & board.white_pieces)
+        | (pawn_attacks(square, true) & pawns & board.black_pieces)
+        | (knight_attacks(square) & board.pieces[Pieces::Knights as usize])
+        | (king_attacks(square) & board.pieces[Pieces::Kings as usize])
+        | (rook_attacks(square, occupied) & rooks_and_queens)
+        | (bishop_attacks(square, occupied) & bishops_and_queens);
+}
+
+/// Whether `move` leaves the opponent in check, directly or by uncovering a slider,
+/// worked out without making it.
+pub fn gives_check(board: &Board, r#move: &Move) -> bool {
+    let white_turn = board.game_state.white_turn;
+    let (own_pieces, enemy_pieces) = if white_turn {
+        (board.white_pieces, board.black_pieces)
+    } else {
+        (board.black_pieces, board.white_pieces)
+    };
+    let king_square = (board.pieces[Pieces::Kings as usize] & enemy_pieces).trailing_zeros();
+
+    let from: Bitboard = 1 << r#move.from;
+    let to: Bitboard = 1 << r#move.to;
+    let mut occupied = (own_pieces | enemy_pieces) & !from | to;
+    let moved_piece = r#move.promotion.unwrap_or(r#move.piece);
+
+    // Our pieces of one type once the move is made
+    let own = |piece: Pieces| {
+        let pieces = board.pieces[piece as usize] & own_pieces & !from;
+        return if piece == moved_piece {
+            pieces | to
+        } else {
+            pieces
+        };
+    };
+    let mut rooks = own(Pieces::Rooks);
+
+    if board.is_en_passant(r#move) {
+        occupied &= !(1 << Board::en_passant_victim(r#move.to, white_turn));
+    } else if Board::is_castling(r#move) {
+        let (rook_from, rook_to) = Board::castling_rook_squares(r#move.to);
+        occupied ^= (1 << rook_from) | (1 << rook_to);
+        rooks ^= (1 << rook_from) | (1 << rook_to);
+    }
+
+    let queens = own(Pieces::Queens);
+
+    return pawn_attacks(king_square, !white_turn) & own(Pieces::Pawns) > 0
+        || knight_attacks(king_square) & own(Pieces::Knights) > 0
+        || rook_attacks(king_square, occupied) & (rooks | queens) > 0
+        || bishop_attacks(king_square, occupied) & (own(Pieces::Bishops) | queens) > 0;
+}
+
+pub fn is_square_attacked(board: &Board, square: u32, by_white: bool) -> bool {
+    let attackers = if by_white {
+        board.white_pieces
+    } else {
+        board.black_pieces
+    };
+    let occupied = board.white_pieces | board.black_pieces;
+
+    return attackers_to(board, square, occupied) & attackers > 0;
+}
+
+struct CastlingPath {
+    king_from: u32,
+    king_to: u32,
+    rook_from: u32,
+    /// Squares that have to be empty between king and rook.
+    empty: Bitboard,
+    /// Squares the king starts on, passes over and lands on.
+    safe: [u32; 3],
+}
+
+const WHITE_KING_SIDE: CastlingPath = CastlingPath {
+    king_from: 60,
+    king_to: 62,
+    rook_from: 63,
+    empty: 1 << 61 | 1 << 62,
+    safe: [60, 61, 62],
+};
+
+const WHITE_QUEEN_SIDE: CastlingPath = CastlingPath {
+    king_from: 60,
+    king_to: 58,
+    rook_from: 56,
+    empty: 1 << 57 | 1 << 58 | 1 << 59,
+    safe: [60, 59, 58],
+};
+
+const BLACK_KING_SIDE: CastlingPath = CastlingPath {
+    king_from: 4,
+    king_to: 6,
+    rook_from: 7,
+    empty: 1 << 5 | 1 << 6,
+    safe: [4, 5, 6],
+};
+
+const BLACK_QUEEN_SIDE: CastlingPath = CastlingPath {
+    king_from: 4,
+    king_to: 2,
+    rook_from: 0,
+    empty: 1 << 1 | 1 << 2 | 1 << 3,
+    safe: [4, 3, 2],
+};
+
+fn castling_moves(board: &Board, white_turn: bool) -> Vec<Move> {
+    let mut moves: Vec<Move> = Vec::new();
+    let castling = &board.game_state.castling;
+
+    let (own_pieces, paths) = if white_turn {
+        (
+            board.white_pieces,
+            [
+                (castling.white_king_side, WHITE_KING_SIDE),
+                (castling.white_queen_side, WHITE_QUEEN_SIDE),
+            ],
+        )
+    } else {
+        (
+            board.black_pieces,
+            [
+                (castling.black_king_side, BLACK_KING_SIDE),
+                (castling.black_queen_side, BLACK_QUEEN_SIDE),
+            ],
+        )
+    };
+
+    let all_pieces = board.white_pieces | board.black_pieces;
+    let kings = board.pieces[Pieces::Kings as usize] & own_pieces;
+    let rooks = board.pieces[Pieces::Rooks as usize] & own_pieces;
+
+    for (allowed, path) in paths {
+        if !allowed
+            || kings & (1 << path.king_from) == 0
+            || rooks & (1 << path.rook_from) == 0
+            || all_pieces & path.empty > 0
+        {
+            continue;
+        }
+
+        if path
+            .safe
+            .iter()
+            .any(|&square| is_square_attacked(board, square, !white_turn))
+        {
+            continue;
+        }
+
+        moves.push(Move {
+            from: path.king_from,
+            to: path.king_to,
+            piece: Pieces::Kings,
+            promotion: None,
+        });
     }
 
     return moves;
 }
 
+fn king_moves(board: &Board, white_turn: bool, mode: GenerationMode) -> Vec<Move> {
+    let mut moves: Vec<Move> = Vec::new();
+
+    let own_pieces = if white_turn {
+        board.white_pieces
+    } else {
+        board.black_pieces
+    };
+    let targets = move_targets(board, own_pieces, mode);
+
+    let mut kings = board.pieces[Pieces::Kings as usize] & own_pieces;
+
+    while kings > 0 {
+        let from = kings.trailing_zeros();
+        let mut king_moves = king_attacks(from) & targets;
+
+        while king_moves > 0 {
+            let to = king_moves.trailing_zeros();
+            moves.push(Move {
+                from,
+                to,
+                piece: Pieces::Kings,
+                promotion: None,
+            });
+
+            king_moves &= !(1 << to);
+        }
+
+        kings &= !(1 << from);
+    }
+
+    if mode != GenerationMode::Captures {
+        moves.extend(castling_moves(board, white_turn));
+    }
+
+    return moves;
+}
+
+fn pseudo_legal_moves(board: &Board, white_turn: bool, mode: GenerationMode) -> Vec<Move> {
+    let mut moves: Vec<Move> = Vec::new();
+
+    moves.extend(pawn_moves_single_push(board, white_turn, mode));
+    if mode != GenerationMode::Captures {
+        moves.extend(pawn_moves_double_push(board, white_turn));
+    }
+    if mode != GenerationMode::Quiets {
+        moves.extend(attacking_pawn_moves(board));
+    }
+    moves.extend(knight_moves(board, white_turn, mode));
+    moves.extend(bishop_moves(board, white_turn, mode));
+    moves.extend(rook_moves(board, white_turn, mode));
+    moves.extend(queen_moves(board, white_turn, mode));
+    moves.extend(king_moves(board, white_turn, mode));
+
+    return moves;
+}
+
+pub const ALL_DIRECTIONS: [Direction; 8] = [
+    Direction::North,
+    Direction::NorthEast,
+    Direction::East,
+    Direction::SouthEast,
+    Direction::South,
+    Direction::SouthWest,
+    Direction::West,
+    Direction::NorthWest,
+];
+
+pub fn is_diagonal(direction: Direction) -> bool {
+    return matches!(
+        direction,
+        Direction::NorthEast | Direction::SouthEast | Direction::SouthWest | Direction::NorthWest
+    );
+}
+
+/// Squares each pinned piece may still move to: the line between its king and the
+/// pinning slider, including the pinner. Unpinned pieces can move anywhere.
+fn pin_masks(board: &Board, king_square: u32, white_turn: bool) -> [Bitboard; 64] {
+    let mut pin_masks = [Bitboard::MAX; 64];
+
+    let (own_pieces, enemy_pieces) = if white_turn {
+        (board.white_pieces, board.black_pieces)
+    } else {
+        (board.black_pieces, board.white_pieces)
+    };
+    let occupied = own_pieces | enemy_pieces;
+    let queens = board.pieces[Pieces::Queens as usize];
+
+    for direction in ALL_DIRECTIONS {
+        let sliders = if is_diagonal(direction) {
+            board.pieces[Pieces::Bishops as usize] | queens
+        } else {
+            board.pieces[Pieces::Rooks as usize] | queens
+        } & enemy_pieces;
+
+        let blockers = ray_attacks(king_square, occupied, direction) & own_pieces;
+        if blockers == 0 {
+            continue;
+        }
+
+        let pin_ray = ray_attacks(king_square, occupied & !blockers, direction);
+        if pin_ray & sliders > 0 {
+            pin_masks[blockers.trailing_zeros() as usize] = pin_ray;
+        }
+    }
+
+    return pin_masks;
+}
+
+/// Squares a non-king move has to land on to get out of check: the checking piece
+/// itself and, for a slider, the squares between it and the king.
+fn check_mask(board: &Board, king_square: u32, checkers: Bitboard) -> Bitboard {
+    if checkers == 0 {
+        return Bitboard::MAX;
+    }
+
+    let occupied = board.white_pieces | board.black_pieces;
+
+    for direction in ALL_DIRECTIONS {
+        let ray = ray_attacks(king_square, occupied, direction);
+        if ray & checkers > 0 {
+            return ray;
+        }
+    }
+
+    return checkers;
+}
+
+fn is_legal_en_passant(board: &Board, r#move: &Move, king_square: u32, white_turn: bool) -> bool {
+    let victim = if white_turn {
+        r#move.to + 8
+    } else {
+        r#move.to - 8
+    };
+
+    let enemy_pieces = if white_turn {
+        board.black_pieces
+    } else {
+        board.white_pieces
+    };
+
+    // Both pawns leave the rank at once, which can expose the king to a slider
+    let occupied = (board.white_pieces | board.black_pieces) ^ (1 << r#move.from) ^ (1 << victim)
+        | (1 << r#move.to);
+
+    return attackers_to(board, king_square, occupied) & enemy_pieces & !(1 << victim) == 0;
+}
+
+/// All moves for the side to move that don't leave its own king in check.
+pub fn legal_moves(board: &Board) -> Vec<Move> {
+    return generate_legal_moves(board, GenerationMode::All);
+}
+
+/// The legal captures and promotions for the side to move.
+pub fn legal_captures(board: &Board) -> Vec<Move> {
+    return generate_legal_moves(board, GenerationMode::Captures);
+}
+
+/// The legal moves that neither capture nor promote.
+pub fn legal_quiets(board: &Board) -> Vec<Move> {
+    return generate_legal_moves(board, GenerationMode::Quiets);
+}
+
+fn generate_legal_moves(board: &Board, mode: GenerationMode) -> Vec<Move> {
+    let white_turn = board.game_state.white_turn;
+    let moves = pseudo_legal_moves(board, white_turn, mode);
+
+    let (own_pieces, enemy_pieces) = if white_turn {
+        (board.white_pieces, board.black_pieces)
+    } else {
+        (board.black_pieces, board.white_pieces)
+    };
+
+    let kings = board.pieces[Pieces::Kings as usize] & own_pieces;
+    if kings == 0 {
+        return moves;
+    }
+
+    let king_square = kings.trailing_zeros();
+    let occupied = own_pieces | enemy_pieces;
+    let checkers = attackers_to(board, king_square, occupied) & enemy_pieces;
+    let check_mask = check_mask(board, king_square, checkers);
+    let pin_masks = pin_masks(board, king_square, white_turn);
+
+    return moves
+        .into_iter()
+        .filter(|r#move| {
+            if r#move.piece == Pieces::Kings {
+                let occupied_without_king = occupied & !(1 << king_square);
+                return attackers_to(board, r#move.to, occupied_without_king) & enemy_pieces == 0;
+            }
+
+            if checkers.count_ones() > 1 {
+                return false;
+            }
+
+            if r#move.piece == Pieces::Pawns
+                && board.game_state.en_passant == Some(r#move.to)
+                && r#move.from % 8 != r#move.to % 8
+            {
+                return pin_masks[r#move.from as usize] & (1 << r#move.to) > 0
+                    && is_legal_en_passant(board, r#move, king_square, white_turn);
+            }
+
+            return check_mask & pin_masks[r#move.from as usize] & (1 << r#move.to) > 0;
+        })
+        .collect();
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn gives_check_matches_making_the_move() {
+        let mut checks: Vec<String> = Vec::new();
+
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            // Castling with the rook landing on the king's file
+            "5k2/8/8/8/8/8/8/4K2R w K - 0 1",
+            // Taking en passant uncovers the rook on h5
+            "8/8/8/1k1pP2R/8/8/8/4K3 w - d6 0 1",
+            // Only some promotions check
+            "3k4/P7/8/8/8/8/8/4K3 w - - 0 1",
+        ] {
+            let mut board = Board::from_fen(fen).unwrap();
+
+            for r#move in legal_moves(&board) {
+                let expected = {
+                    let undo = board.make_move(&r#move);
+                    let white_turn = board.game_state.white_turn;
+                    let own_pieces = if white_turn {
+                        board.white_pieces
+                    } else {
+                        board.black_pieces
+                    };
+                    let king = board.pieces[Pieces::Kings as usize] & own_pieces;
+                    let checked = is_square_attacked(&board, king.trailing_zeros(), !white_turn);
+                    board.unmake_move(&r#move, undo);
+                    checked
+                };
+
+                assert_eq!(expected, gives_check(&board, &r#move), "{} {}", fen, r#move);
+                if expected {
+                    checks.push(r#move.to_string());
+                }
+            }
+        }
+
+        for check in ["e1g1", "e5d6", "a7a8q", "a7a8r"] {
+            assert!(checks.contains(&check.to_string()), "{}", check);
+        }
+        assert!(!checks.contains(&"a7a8n".to_string()));
+    }
+
+    #[test]
+    fn attack_rays_match_the_generator() {
+        assert_eq!(create_attack_rays(), ATTACK_RAYS);
+    }
+
     #[test]
     fn single_push_pawn_moves() {
-        let board = Board::from_fen("r1bk3r/p2pBpNp/n4n2/1p1NP2P/6P1/3P4/P1P1K3/q5b1 w KQkq - 0 1");
+        let board = Board::from_fen("r1bk3r/p2pBpNp/n4n2/1p1NP2P/6P1/3P4/P1P1K3/q5b1 w KQkq - 0 1")
+            .unwrap();
 
         let expected_white_moves = vec![
             Move {
                 from: 28,
                 to: 20,
                 piece: Pieces::Pawns,
+                promotion: None,
             },
             Move {
                 from: 31,
                 to: 23,
                 piece: Pieces::Pawns,
+                promotion: None,
             },
             Move {
                 from: 38,
                 to: 30,
                 piece: Pieces::Pawns,
+                promotion: None,
             },
             Move {
                 from: 43,
                 to: 35,
                 piece: Pieces::Pawns,
+                promotion: None,
             },
             Move {
                 from: 48,
                 to: 40,
                 piece: Pieces::Pawns,
+                promotion: None,
             },
             Move {
                 from: 50,
                 to: 42,
                 piece: Pieces::Pawns,
+                promotion: None,
             },
         ];
-        let white_moves = pawn_moves_single_push(&board, true);
+        let white_moves = pawn_moves_single_push(&board, true, GenerationMode::All);
         assert_eq!(expected_white_moves, white_moves);
 
         let expected_black_moves = vec![
@@ -1128,36 +1570,42 @@ mod tests {
                 from: 11,
                 to: 19,
                 piece: Pieces::Pawns,
+                promotion: None,
             },
             Move {
                 from: 15,
                 to: 23,
                 piece: Pieces::Pawns,
+                promotion: None,
             },
             Move {
                 from: 25,
                 to: 33,
                 piece: Pieces::Pawns,
+                promotion: None,
             },
         ];
-        let black_moves = pawn_moves_single_push(&board, false);
+        let black_moves = pawn_moves_single_push(&board, false, GenerationMode::All);
         assert_eq!(expected_black_moves, black_moves);
     }
 
     #[test]
     fn double_push_pawn_moves() {
-        let board = Board::from_fen("r1bk3r/p2pBpNp/n4n2/1p1NP2P/6P1/3P4/P1P1K3/q5b1 w KQkq - 0 1");
+        let board = Board::from_fen("r1bk3r/p2pBpNp/n4n2/1p1NP2P/6P1/3P4/P1P1K3/q5b1 w KQkq - 0 1")
+            .unwrap();
 
         let expected_white_moves = vec![
             Move {
                 from: 48,
                 to: 32,
                 piece: Pieces::Pawns,
+                promotion: None,
             },
             Move {
                 from: 50,
                 to: 34,
                 piece: Pieces::Pawns,
+                promotion: None,
             },
         ];
         let white_moves = pawn_moves_double_push(&board, true);
@@ -1170,40 +1618,47 @@ mod tests {
 
     #[test]
     fn find_attacking_pawn_moves() {
-        let board = Board::from_fen("2k5/2N2N2/1PP3rp/5n1b/q1pB1nB1/Pp2PpQ1/R1RpP3/1K6 w - - 0 1");
+        let board =
+            Board::from_fen("2k5/2N2N2/1PP3rp/5n1b/q1pB1nB1/Pp2PpQ1/R1RpP3/1K6 w - - 0 1").unwrap();
 
         let expected_white_moves = vec![
             Move {
                 from: 44,
                 to: 37,
                 piece: Pieces::Pawns,
+                promotion: None,
             },
             Move {
                 from: 52,
                 to: 45,
                 piece: Pieces::Pawns,
+                promotion: None,
             },
         ];
         let white_moves = attacking_pawn_moves(&board);
         assert_eq!(expected_white_moves, white_moves);
 
-        let board = Board::from_fen("2k5/2N2N2/1PP3rp/5n1b/q1pB1nB1/Pp2PpQ1/R1RpP3/1K6 b - - 0 1");
+        let board =
+            Board::from_fen("2k5/2N2N2/1PP3rp/5n1b/q1pB1nB1/Pp2PpQ1/R1RpP3/1K6 b - - 0 1").unwrap();
 
         let expected_black_moves = vec![
             Move {
                 from: 41,
                 to: 48,
                 piece: Pieces::Pawns,
+                promotion: None,
             },
             Move {
                 from: 41,
                 to: 50,
                 piece: Pieces::Pawns,
+                promotion: None,
             },
             Move {
                 from: 45,
                 to: 52,
                 piece: Pieces::Pawns,
+                promotion: None,
             },
         ];
         let black_moves = attacking_pawn_moves(&board);
@@ -1213,94 +1668,142 @@ mod tests {
     #[test]
     fn find_en_passant_moves() {
         let board =
-            Board::from_fen("rnbqk1nr/ppp2pbp/3p2p1/3Pp3/4P3/5P2/PPP3PP/RNBKQBNR w - e6 0 1");
+            Board::from_fen("rnbqk1nr/ppp2pbp/3p2p1/3Pp3/4P3/5P2/PPP3PP/RNBKQBNR w - e6 0 1")
+                .unwrap();
 
         let expected_white_moves: Vec<Move> = vec![Move {
             from: 27,
             to: 20,
             piece: Pieces::Pawns,
+            promotion: None,
         }];
         let white_moves = attacking_pawn_moves(&board);
         assert_eq!(expected_white_moves, white_moves);
 
         let board =
-            Board::from_fen("rnbqk1nr/ppp2pb1/3p2p1/3Pp1Pp/4P3/5P2/PPP4P/RNBKQBNR w - h6 0 1");
+            Board::from_fen("rnbqk1nr/ppp2pb1/3p2p1/3Pp1Pp/4P3/5P2/PPP4P/RNBKQBNR w - h6 0 1")
+                .unwrap();
         let expected_white_moves: Vec<Move> = vec![Move {
             from: 30,
             to: 23,
             piece: Pieces::Pawns,
+            promotion: None,
         }];
         let white_moves = attacking_pawn_moves(&board);
         assert_eq!(expected_white_moves, white_moves);
 
         let board =
-            Board::from_fen("rnbqk1nr/1pp2pb1/3p2p1/3Pp1Pp/pP2P3/5P2/P1P4P/RNBKQBNR b - b3 0 1");
+            Board::from_fen("rnbqk1nr/1pp2pb1/3p2p1/3Pp1Pp/pP2P3/5P2/P1P4P/RNBKQBNR b - b3 0 1")
+                .unwrap();
         let expected_black_moves = vec![Move {
             from: 32,
             to: 41,
             piece: Pieces::Pawns,
+            promotion: None,
         }];
         let black_moves = attacking_pawn_moves(&board);
         assert_eq!(expected_black_moves, black_moves);
     }
 
+    #[test]
+    fn find_promotion_moves() {
+        let board = Board::from_fen("1n2k3/P7/8/8/8/8/5p2/K5N1 w - - 0 1").unwrap();
+
+        let promotions = |from, to| -> Vec<Move> {
+            PROMOTION_PIECES
+                .iter()
+                .map(|&piece| Move {
+                    from,
+                    to,
+                    piece: Pieces::Pawns,
+                    promotion: Some(piece),
+                })
+                .collect()
+        };
+
+        assert_eq!(
+            promotions(8, 0),
+            pawn_moves_single_push(&board, true, GenerationMode::All)
+        );
+        assert_eq!(promotions(8, 1), attacking_pawn_moves(&board));
+
+        let board = Board::from_fen("1n2k3/P7/8/8/8/8/5p2/K5N1 b - - 0 1").unwrap();
+
+        assert_eq!(
+            promotions(53, 61),
+            pawn_moves_single_push(&board, false, GenerationMode::All)
+        );
+        assert_eq!(promotions(53, 62), attacking_pawn_moves(&board));
+    }
+
     #[test]
     fn find_knight_moves() {
-        let board = Board::from_fen("r1bk3r/p2pBpNp/n4n2/1p1NP2P/6P1/3P4/P1P1K3/q5b1 w KQkq - 0 1");
+        let board = Board::from_fen("r1bk3r/p2pBpNp/n4n2/1p1NP2P/6P1/3P4/P1P1K3/q5b1 w KQkq - 0 1")
+            .unwrap();
 
         let expected_white_moves = vec![
             Move {
                 from: 14,
                 to: 29,
                 piece: Pieces::Knights,
+                promotion: None,
             },
             Move {
                 from: 14,
                 to: 20,
                 piece: Pieces::Knights,
+                promotion: None,
             },
             Move {
                 from: 14,
                 to: 4,
                 piece: Pieces::Knights,
+                promotion: None,
             },
             Move {
                 from: 27,
                 to: 44,
                 piece: Pieces::Knights,
+                promotion: None,
             },
             Move {
                 from: 27,
                 to: 37,
                 piece: Pieces::Knights,
+                promotion: None,
             },
             Move {
                 from: 27,
                 to: 21,
                 piece: Pieces::Knights,
+                promotion: None,
             },
             Move {
                 from: 27,
                 to: 42,
                 piece: Pieces::Knights,
+                promotion: None,
             },
             Move {
                 from: 27,
                 to: 33,
                 piece: Pieces::Knights,
+                promotion: None,
             },
             Move {
                 from: 27,
                 to: 17,
                 piece: Pieces::Knights,
+                promotion: None,
             },
             Move {
                 from: 27,
                 to: 10,
                 piece: Pieces::Knights,
+                promotion: None,
             },
         ];
-        let white_moves = knight_moves(&board, true);
+        let white_moves = knight_moves(&board, true, GenerationMode::All);
         assert_eq!(expected_white_moves, white_moves);
 
         let expected_black_moves = vec![
@@ -1308,159 +1811,188 @@ mod tests {
                 from: 16,
                 to: 33,
                 piece: Pieces::Knights,
+                promotion: None,
             },
             Move {
                 from: 16,
                 to: 26,
                 piece: Pieces::Knights,
+                promotion: None,
             },
             Move {
                 from: 16,
                 to: 10,
                 piece: Pieces::Knights,
+                promotion: None,
             },
             Move {
                 from: 16,
                 to: 1,
                 piece: Pieces::Knights,
+                promotion: None,
             },
             Move {
                 from: 21,
                 to: 38,
                 piece: Pieces::Knights,
+                promotion: None,
             },
             Move {
                 from: 21,
                 to: 31,
                 piece: Pieces::Knights,
+                promotion: None,
             },
             Move {
                 from: 21,
                 to: 6,
                 piece: Pieces::Knights,
+                promotion: None,
             },
             Move {
                 from: 21,
                 to: 36,
                 piece: Pieces::Knights,
+                promotion: None,
             },
             Move {
                 from: 21,
                 to: 27,
                 piece: Pieces::Knights,
+                promotion: None,
             },
             Move {
                 from: 21,
                 to: 4,
                 piece: Pieces::Knights,
+                promotion: None,
             },
         ];
-        let black_moves = knight_moves(&board, false);
+        let black_moves = knight_moves(&board, false, GenerationMode::All);
         assert_eq!(expected_black_moves, black_moves);
     }
 
     #[test]
     fn find_rook_moves() {
-        let board = Board::from_fen("8/8/8/1k2N1B1/3p4/1K2p1Q1/1R5R/5b1r w - - 0 1");
+        let board = Board::from_fen("8/8/8/1k2N1B1/3p4/1K2p1Q1/1R5R/5b1r w - - 0 1").unwrap();
 
         let expected_white_moves = vec![
+            Move {
+                from: 49,
+                to: 48,
+                piece: Pieces::Rooks,
+                promotion: None,
+            },
             Move {
                 from: 49,
                 to: 50,
                 piece: Pieces::Rooks,
+                promotion: None,
             },
             Move {
                 from: 49,
                 to: 51,
                 piece: Pieces::Rooks,
+                promotion: None,
             },
             Move {
                 from: 49,
                 to: 52,
                 piece: Pieces::Rooks,
+                promotion: None,
             },
             Move {
                 from: 49,
                 to: 53,
                 piece: Pieces::Rooks,
+                promotion: None,
             },
             Move {
                 from: 49,
                 to: 54,
                 piece: Pieces::Rooks,
+                promotion: None,
             },
             Move {
                 from: 49,
                 to: 57,
                 piece: Pieces::Rooks,
-            },
-            Move {
-                from: 49,
-                to: 48,
-                piece: Pieces::Rooks,
+                promotion: None,
             },
             Move {
                 from: 55,
                 to: 7,
                 piece: Pieces::Rooks,
+                promotion: None,
             },
             Move {
                 from: 55,
                 to: 15,
                 piece: Pieces::Rooks,
+                promotion: None,
             },
             Move {
                 from: 55,
                 to: 23,
                 piece: Pieces::Rooks,
+                promotion: None,
             },
             Move {
                 from: 55,
                 to: 31,
                 piece: Pieces::Rooks,
+                promotion: None,
             },
             Move {
                 from: 55,
                 to: 39,
                 piece: Pieces::Rooks,
+                promotion: None,
             },
             Move {
                 from: 55,
                 to: 47,
                 piece: Pieces::Rooks,
-            },
-            Move {
-                from: 55,
-                to: 63,
-                piece: Pieces::Rooks,
+                promotion: None,
             },
             Move {
                 from: 55,
                 to: 50,
                 piece: Pieces::Rooks,
+                promotion: None,
             },
             Move {
                 from: 55,
                 to: 51,
                 piece: Pieces::Rooks,
+                promotion: None,
             },
             Move {
                 from: 55,
                 to: 52,
                 piece: Pieces::Rooks,
+                promotion: None,
             },
             Move {
                 from: 55,
                 to: 53,
                 piece: Pieces::Rooks,
+                promotion: None,
             },
             Move {
                 from: 55,
                 to: 54,
                 piece: Pieces::Rooks,
+                promotion: None,
+            },
+            Move {
+                from: 55,
+                to: 63,
+                piece: Pieces::Rooks,
+                promotion: None,
             },
         ];
-        let white_moves = rook_moves(&board, true);
+        let white_moves = rook_moves(&board, true, GenerationMode::All);
         assert_eq!(expected_white_moves, white_moves);
 
         let expected_black_moves = vec![
@@ -1468,79 +2000,93 @@ mod tests {
                 from: 63,
                 to: 55,
                 piece: Pieces::Rooks,
+                promotion: None,
             },
             Move {
                 from: 63,
                 to: 62,
                 piece: Pieces::Rooks,
+                promotion: None,
             },
         ];
-        let black_moves = rook_moves(&board, false);
+        let black_moves = rook_moves(&board, false, GenerationMode::All);
         assert_eq!(expected_black_moves, black_moves);
     }
 
     #[test]
     fn find_bishop_moves() {
-        let board = Board::from_fen("8/2prkp2/1nQpB1p1/1p2Pn2/p4Pq1/1b3N2/3B3K/2R2R2 w - - 0 1");
+        let board =
+            Board::from_fen("8/2prkp2/1nQpB1p1/1p2Pn2/p4Pq1/1b3N2/3B3K/2R2R2 w - - 0 1").unwrap();
 
         let expected_white_moves: Vec<Move> = vec![
             Move {
                 from: 20,
-                to: 13,
+                to: 11,
                 piece: Pieces::Bishops,
+                promotion: None,
             },
             Move {
                 from: 20,
-                to: 29,
+                to: 13,
                 piece: Pieces::Bishops,
+                promotion: None,
             },
             Move {
                 from: 20,
                 to: 27,
                 piece: Pieces::Bishops,
+                promotion: None,
             },
             Move {
                 from: 20,
-                to: 34,
+                to: 29,
                 piece: Pieces::Bishops,
+                promotion: None,
             },
             Move {
                 from: 20,
-                to: 41,
+                to: 34,
                 piece: Pieces::Bishops,
+                promotion: None,
             },
             Move {
                 from: 20,
-                to: 11,
+                to: 41,
                 piece: Pieces::Bishops,
+                promotion: None,
             },
             Move {
                 from: 51,
-                to: 44,
+                to: 24,
                 piece: Pieces::Bishops,
+                promotion: None,
             },
             Move {
                 from: 51,
-                to: 60,
+                to: 33,
                 piece: Pieces::Bishops,
+                promotion: None,
             },
             Move {
                 from: 51,
-                to: 24,
+                to: 42,
                 piece: Pieces::Bishops,
+                promotion: None,
             },
             Move {
                 from: 51,
-                to: 33,
+                to: 44,
                 piece: Pieces::Bishops,
+                promotion: None,
             },
             Move {
                 from: 51,
-                to: 42,
+                to: 60,
                 piece: Pieces::Bishops,
+                promotion: None,
             },
         ];
-        let white_moves = bishop_moves(&board, true);
+        let white_moves = bishop_moves(&board, true, GenerationMode::All);
         assert_eq!(expected_white_moves, white_moves);
 
         let expected_black_moves: Vec<Move> = vec![
@@ -1548,234 +2094,574 @@ mod tests {
                 from: 41,
                 to: 20,
                 piece: Pieces::Bishops,
+                promotion: None,
             },
             Move {
                 from: 41,
                 to: 27,
                 piece: Pieces::Bishops,
+                promotion: None,
             },
             Move {
                 from: 41,
                 to: 34,
                 piece: Pieces::Bishops,
+                promotion: None,
             },
             Move {
                 from: 41,
-                to: 50,
+                to: 48,
                 piece: Pieces::Bishops,
+                promotion: None,
             },
             Move {
                 from: 41,
-                to: 59,
+                to: 50,
                 piece: Pieces::Bishops,
+                promotion: None,
             },
             Move {
                 from: 41,
-                to: 48,
+                to: 59,
                 piece: Pieces::Bishops,
+                promotion: None,
             },
         ];
-        let black_moves = bishop_moves(&board, false);
+        let black_moves = bishop_moves(&board, false, GenerationMode::All);
         assert_eq!(expected_black_moves, black_moves);
     }
 
     #[test]
     fn find_queen_moves() {
-        let board = Board::from_fen("6Nr/5P1p/8/P5q1/3p3B/nK4Q1/RP1Pk3/1R2nb2 w - - 0 1");
+        let board = Board::from_fen("6Nr/5P1p/8/P5q1/3p3B/nK4Q1/RP1Pk3/1R2nb2 w - - 0 1").unwrap();
 
         let expected_white_moves: Vec<Move> = vec![
             Move {
                 from: 46,
-                to: 30,
-                piece: Pieces::Queens,
-            },
-            Move {
-                from: 46,
-                to: 38,
+                to: 1,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 46,
-                to: 47,
+                to: 10,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 46,
-                to: 55,
+                to: 19,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 46,
-                to: 54,
+                to: 28,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 46,
-                to: 62,
+                to: 30,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 46,
-                to: 53,
+                to: 37,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 46,
-                to: 60,
+                to: 38,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 46,
                 to: 42,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 46,
                 to: 43,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 46,
                 to: 44,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 46,
                 to: 45,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 46,
-                to: 1,
+                to: 47,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 46,
-                to: 10,
+                to: 53,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 46,
-                to: 19,
+                to: 54,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 46,
-                to: 28,
+                to: 55,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 46,
-                to: 37,
+                to: 60,
                 piece: Pieces::Queens,
+                promotion: None,
+            },
+            Move {
+                from: 46,
+                to: 62,
+                piece: Pieces::Queens,
+                promotion: None,
             },
         ];
-        let white_moves = queen_moves(&board, true);
+        let white_moves = queen_moves(&board, true, GenerationMode::All);
         assert_eq!(expected_white_moves, white_moves);
 
         let expected_black_moves: Vec<Move> = vec![
             Move {
                 from: 30,
-                to: 6,
+                to: 3,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 30,
-                to: 14,
+                to: 6,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 30,
-                to: 22,
+                to: 12,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 30,
-                to: 23,
+                to: 14,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 30,
-                to: 31,
+                to: 21,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 30,
-                to: 39,
+                to: 22,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 30,
-                to: 38,
+                to: 23,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 30,
-                to: 46,
+                to: 24,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 30,
-                to: 37,
+                to: 25,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 30,
-                to: 44,
+                to: 26,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 30,
-                to: 51,
+                to: 27,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 30,
-                to: 24,
+                to: 28,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 30,
-                to: 25,
+                to: 29,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 30,
-                to: 26,
+                to: 31,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 30,
-                to: 27,
+                to: 37,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 30,
-                to: 28,
+                to: 38,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 30,
-                to: 29,
+                to: 39,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 30,
-                to: 3,
+                to: 44,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 30,
-                to: 12,
+                to: 46,
                 piece: Pieces::Queens,
+                promotion: None,
             },
             Move {
                 from: 30,
-                to: 21,
+                to: 51,
                 piece: Pieces::Queens,
+                promotion: None,
             },
         ];
-        let black_moves = queen_moves(&board, false);
+        let black_moves = queen_moves(&board, false, GenerationMode::All);
         assert_eq!(expected_black_moves, black_moves);
     }
+
+    #[test]
+    fn find_king_moves() {
+        let board = Board::from_fen("8/8/8/8/8/8/6pP/6Kn w - - 0 1").unwrap();
+
+        let expected_white_moves = vec![
+            Move {
+                from: 62,
+                to: 53,
+                piece: Pieces::Kings,
+                promotion: None,
+            },
+            Move {
+                from: 62,
+                to: 54,
+                piece: Pieces::Kings,
+                promotion: None,
+            },
+            Move {
+                from: 62,
+                to: 61,
+                piece: Pieces::Kings,
+                promotion: None,
+            },
+            Move {
+                from: 62,
+                to: 63,
+                piece: Pieces::Kings,
+                promotion: None,
+            },
+        ];
+        let white_moves = king_moves(&board, true, GenerationMode::All);
+        assert_eq!(expected_white_moves, white_moves);
+
+        let board = Board::from_fen("k7/8/8/8/8/8/8/8 b - - 0 1").unwrap();
+
+        let expected_black_moves = vec![
+            Move {
+                from: 0,
+                to: 1,
+                piece: Pieces::Kings,
+                promotion: None,
+            },
+            Move {
+                from: 0,
+                to: 8,
+                piece: Pieces::Kings,
+                promotion: None,
+            },
+            Move {
+                from: 0,
+                to: 9,
+                piece: Pieces::Kings,
+                promotion: None,
+            },
+        ];
+        let black_moves = king_moves(&board, false, GenerationMode::All);
+        assert_eq!(expected_black_moves, black_moves);
+    }
+
+    #[test]
+    fn find_castling_moves() {
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+
+        let expected_white_moves = vec![
+            Move {
+                from: 60,
+                to: 62,
+                piece: Pieces::Kings,
+                promotion: None,
+            },
+            Move {
+                from: 60,
+                to: 58,
+                piece: Pieces::Kings,
+                promotion: None,
+            },
+        ];
+        assert_eq!(expected_white_moves, castling_moves(&board, true));
+
+        let expected_black_moves = vec![
+            Move {
+                from: 4,
+                to: 6,
+                piece: Pieces::Kings,
+                promotion: None,
+            },
+            Move {
+                from: 4,
+                to: 2,
+                piece: Pieces::Kings,
+                promotion: None,
+            },
+        ];
+        assert_eq!(expected_black_moves, castling_moves(&board, false));
+
+        // Only the rights that are still available
+        let board = Board::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w Kq - 0 1").unwrap();
+        assert_eq!(expected_white_moves[..1], castling_moves(&board, true));
+        assert_eq!(expected_black_moves[1..], castling_moves(&board, false));
+
+        // Pieces between king and rook
+        let board = Board::from_fen("rn2k1nr/8/8/8/8/8/8/R2QKB1R w KQkq - 0 1").unwrap();
+        assert_eq!(Vec::<Move>::new(), castling_moves(&board, true));
+        assert_eq!(Vec::<Move>::new(), castling_moves(&board, false));
+    }
+
+    #[test]
+    fn no_castling_out_of_through_or_into_check() {
+        // King in check
+        let board = Board::from_fen("4r1k1/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        assert_eq!(Vec::<Move>::new(), castling_moves(&board, true));
+
+        // Passing over an attacked square
+        let board = Board::from_fen("3r2k1/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        assert_eq!(
+            vec![Move {
+                from: 60,
+                to: 62,
+                piece: Pieces::Kings,
+                promotion: None,
+            }],
+            castling_moves(&board, true)
+        );
+
+        // Landing on an attacked square
+        let board = Board::from_fen("6k1/8/8/8/8/8/8/R3K2R w KQ - 0 1").unwrap();
+        let board_with_knight = Board::from_fen("6k1/8/8/8/8/7n/8/R3K2R w KQ - 0 1").unwrap();
+        assert_eq!(2, castling_moves(&board, true).len());
+        assert_eq!(
+            vec![Move {
+                from: 60,
+                to: 58,
+                piece: Pieces::Kings,
+                promotion: None,
+            }],
+            castling_moves(&board_with_knight, true)
+        );
+
+        // An attacked b-file square doesn't prevent castling queen side
+        let board = Board::from_fen("1r2k3/8/8/8/8/8/8/R3K3 w Q - 0 1").unwrap();
+        assert_eq!(1, castling_moves(&board, true).len());
+    }
+
+    #[test]
+    fn square_attacked_by_each_piece() {
+        let board = Board::from_fen("4k3/8/8/3p4/8/1n6/8/4K2R w - - 0 1").unwrap();
+
+        // Pawn on d5 attacks c4 and e4
+        assert!(is_square_attacked(&board, 34, false));
+        assert!(is_square_attacked(&board, 36, false));
+        assert!(!is_square_attacked(&board, 19, false));
+
+        // Knight on b3 attacks d2 and a1
+        assert!(is_square_attacked(&board, 51, false));
+        assert!(is_square_attacked(&board, 56, false));
+
+        // Rook on h1 up the h-file and along the first rank up to the king
+        assert!(is_square_attacked(&board, 7, true));
+        assert!(is_square_attacked(&board, 61, true));
+        assert!(!is_square_attacked(&board, 57, true));
+
+        // Kings
+        assert!(is_square_attacked(&board, 12, false));
+        assert!(is_square_attacked(&board, 52, true));
+    }
+
+    fn sorted_moves(mut moves: Vec<Move>) -> Vec<(u32, u32)> {
+        moves.sort_by_key(|r#move| (r#move.from, r#move.to));
+        return moves
+            .iter()
+            .map(|r#move| (r#move.from, r#move.to))
+            .collect();
+    }
+
+    #[test]
+    fn pinned_pieces_stay_on_the_pin_line() {
+        // Rook on e4 pinned by the rook on e8, bishop on d2 pinned by the queen on a5
+        let board = Board::from_fen("4r2k/8/8/q7/4R3/8/3B4/4K3 w - - 0 1").unwrap();
+        let moves = legal_moves(&board);
+
+        let rook_moves = sorted_moves(
+            moves
+                .iter()
+                .copied()
+                .filter(|r#move| r#move.piece == Pieces::Rooks)
+                .collect(),
+        );
+        assert_eq!(
+            vec![(36, 4), (36, 12), (36, 20), (36, 28), (36, 44), (36, 52)],
+            rook_moves
+        );
+
+        let bishop_moves = sorted_moves(
+            moves
+                .into_iter()
+                .filter(|r#move| r#move.piece == Pieces::Bishops)
+                .collect(),
+        );
+        assert_eq!(vec![(51, 24), (51, 33), (51, 42)], bishop_moves);
+    }
+
+    #[test]
+    fn only_check_evasions_in_check() {
+        // Bishop on b4 checks the king; block with the queen, capture with the knight or move the king
+        let board = Board::from_fen("4k3/8/8/8/1b6/3N4/8/2Q1K2R w K - 0 1").unwrap();
+        let moves = sorted_moves(legal_moves(&board));
+
+        assert_eq!(
+            vec![
+                (43, 33),
+                (58, 42),
+                (58, 51),
+                (60, 52),
+                (60, 53),
+                (60, 59),
+                (60, 61)
+            ],
+            moves
+        );
+    }
+
+    #[test]
+    fn only_king_moves_in_double_check() {
+        let board = Board::from_fen("4k3/4r3/8/8/1b6/8/8/R3K3 w - - 0 1").unwrap();
+        let moves = legal_moves(&board);
+
+        assert!(!moves.is_empty());
+        assert!(moves.iter().all(|r#move| r#move.piece == Pieces::Kings));
+    }
+
+    #[test]
+    fn king_can_not_step_along_checking_ray() {
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/r3K3 w - - 0 1").unwrap();
+        let moves = sorted_moves(legal_moves(&board));
+
+        assert_eq!(vec![(60, 51), (60, 52), (60, 53)], moves);
+    }
+
+    #[test]
+    fn en_passant_discovered_check() {
+        // Capturing en passant would clear the fifth rank between the king and the rook
+        let board = Board::from_fen("8/8/8/KPp4r/8/8/8/4k3 w - c6 0 1").unwrap();
+        let moves = legal_moves(&board);
+        assert!(!moves
+            .iter()
+            .any(|r#move| r#move.piece == Pieces::Pawns && r#move.to == 18));
+
+        // Without the rook it is fine
+        let board = Board::from_fen("8/8/8/KPp5/8/8/8/4k3 w - c6 0 1").unwrap();
+        let moves = legal_moves(&board);
+        assert!(moves
+            .iter()
+            .any(|r#move| r#move.piece == Pieces::Pawns && r#move.to == 18));
+    }
+
+    #[test]
+    fn en_passant_captures_checking_pawn() {
+        let board = Board::from_fen("8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0 1").unwrap();
+        let moves = legal_moves(&board);
+
+        assert!(moves.contains(&Move {
+            from: 36,
+            to: 43,
+            piece: Pieces::Pawns,
+            promotion: None,
+        }));
+    }
+
+    #[test]
+    fn captures_and_quiets_split_the_moves() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "8/8/8/2k5/3Pp3/8/8/4K3 b - d3 0 1",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+            let occupied = board.white_pieces | board.black_pieces;
+
+            let expected: Vec<Move> = legal_moves(&board)
+                .into_iter()
+                .filter(|r#move| {
+                    occupied & (1 << r#move.to) > 0
+                        || r#move.promotion.is_some()
+                        || (r#move.piece == Pieces::Pawns
+                            && board.game_state.en_passant == Some(r#move.to))
+                })
+                .collect();
+
+            assert_eq!(sorted_moves(expected), sorted_moves(legal_captures(&board)));
+
+            let mut split = legal_captures(&board);
+            split.extend(legal_quiets(&board));
+            assert_eq!(sorted_moves(legal_moves(&board)), sorted_moves(split));
+        }
+    }
 }
@@ -0,0 +1,360 @@
+use crate::{
+    board::Board,
+    move_generation::{legal_captures, legal_quiets},
+    piece::Pieces,
+    r#move::Move,
+    see::{piece_value, see_ge},
+};
+
+/// History scores stay within this bound, so they never overflow however long a
+/// search runs.
+const MAX_HISTORY: i32 = 16_384;
+
+/// Whether a move neither captures nor promotes, the same split `legal_captures` and
+/// `legal_quiets` make.
+pub fn is_quiet(board: &Board, r#move: &Move) -> bool {
+    let occupied = board.white_pieces | board.black_pieces;
+    let is_en_passant =
+        r#move.piece == Pieces::Pawns && board.game_state.en_passant == Some(r#move.to);
+
+    return occupied & (1 << r#move.to) == 0 && r#move.promotion.is_none() && !is_en_passant;
+}
+
+/// Most valuable victim, least valuable attacker. En passant captures an empty square
+/// and counts as a pawn.
+pub fn mvv_lva(board: &Board, r#move: &Move) -> i32 {
+    let victim = board
+        .piece_at(r#move.to)
+        .map_or(piece_value(Pieces::Pawns), piece_value);
+    let promotion = r#move.promotion.map_or(0, piece_value);
+
+    return victim * 10 + promotion - piece_value(r#move.piece) / 10;
+}
+
+/// Two quiet moves per ply that caused a beta cutoff. A move that refutes one line
+/// often refutes its siblings too.
+pub struct Killers {
+    moves: Vec<[Option<Move>; 2]>,
+}
+
+impl Killers {
+    pub fn new(max_ply: usize) -> Self {
+        Killers {
+            moves: vec![[None; 2]; max_ply + 1],
+        }
+    }
+
+    pub fn get(&self, ply: u32) -> [Option<Move>; 2] {
+        return self.moves[ply as usize];
+    }
+
+    pub fn store(&mut self, ply: u32, r#move: Move) {
+        let killers = &mut self.moves[ply as usize];
+
+        if killers[0] != Some(r#move) {
+            killers[1] = killers[0];
+            killers[0] = Some(r#move);
+        }
+    }
+}
+
+/// Butterfly history: how well quiet moves from one square to another did anywhere in
+/// the tree, independent of the piece or position.
+pub struct History {
+    scores: Box<[[i32; 64]; 64]>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        History {
+            scores: Box::new([[0; 64]; 64]),
+        }
+    }
+
+    pub fn get(&self, r#move: &Move) -> i32 {
+        return self.scores[r#move.from as usize][r#move.to as usize];
+    }
+
+    /// Moves the score towards the bound by `bonus`, less so the closer it already is,
+    /// so recent results weigh more than old ones. A negative bonus is a penalty.
+    pub fn update(&mut self, r#move: &Move, bonus: i32) {
+        let bonus = bonus.clamp(-MAX_HISTORY, MAX_HISTORY);
+        let score = &mut self.scores[r#move.from as usize][r#move.to as usize];
+
+        *score += bonus - *score * bonus.abs() / MAX_HISTORY;
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Stage {
+    TableMove,
+    WinningCaptures,
+    Killers,
+    Quiets,
+    LosingCaptures,
+    Done,
+}
+
+/// Hands out the moves of a position best first, generating and ordering each stage
+/// only when the ones before it didn't produce a cutoff:
+///
+/// 1. the transposition table move
+/// 2. captures that don't lose material by static exchange, by MVV-LVA
+/// 3. the killer moves of the ply
+/// 4. the remaining quiet moves, by history
+/// 5. captures that lose material
+///
+/// The table and killer moves may come from other positions, so they are only
+/// returned when they are legal here.
+pub struct MovePicker {
+    stage: Stage,
+    table_move: Option<Move>,
+    killers: [Option<Move>; 2],
+    killer_index: usize,
+    /// Generated on first use and sorted so the best move is last.
+    captures: Option<Vec<Move>>,
+    quiets: Option<Vec<Move>>,
+    losing_captures: Vec<Move>,
+}
+
+impl MovePicker {
+    pub fn new(table_move: Option<Move>, killers: [Option<Move>; 2]) -> Self {
+        MovePicker {
+            stage: Stage::TableMove,
+            table_move,
+            killers,
+            killer_index: 0,
+            captures: None,
+            quiets: None,
+            losing_captures: Vec::new(),
+        }
+    }
+
+    fn captures(&mut self, board: &Board) -> &mut Vec<Move> {
+        return self.captures.get_or_insert_with(|| {
+            let mut captures = legal_captures(board);
+            captures.sort_by_cached_key(|r#move| mvv_lva(board, r#move));
+            captures
+        });
+    }
+
+    fn quiets(&mut self, board: &Board) -> &mut Vec<Move> {
+        return self.quiets.get_or_insert_with(|| legal_quiets(board));
+    }
+
+    /// Whether the quiet `move` was already handed out as the table move or a killer.
+    /// Killers are only returned by their stage when they are quiet here, so captures
+    /// are checked against the table move alone.
+    fn already_picked(&self, r#move: &Move) -> bool {
+        return self.table_move == Some(*r#move) || self.killers.contains(&Some(*r#move));
+    }
+
+    /// The next move to search, or `None` when every legal move was returned. `board`
+    /// has to be the position the picker was made for.
+    pub fn next(&mut self, board: &Board, history: &History) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::TableMove => {
+                    self.stage = Stage::WinningCaptures;
+
+                    if let Some(table_move) = self.table_move {
+                        let moves = if is_quiet(board, &table_move) {
+                            self.quiets(board)
+                        } else {
+                            self.captures(board)
+                        };
+
+                        if moves.contains(&table_move) {
+                            return Some(table_move);
+                        }
+                        self.table_move = None;
+                    }
+                }
+                Stage::WinningCaptures => {
+                    let Some(r#move) = self.captures(board).pop() else {
+                        self.stage = Stage::Killers;
+                        continue;
+                    };
+
+                    if self.table_move == Some(r#move) {
+                        continue;
+                    }
+
+                    if see_ge(board, &r#move, 0) {
+                        return Some(r#move);
+                    }
+                    // Captures are popped best first, so the losing ones stay in order
+                    self.losing_captures.push(r#move);
+                }
+                Stage::Killers => {
+                    let Some(&killer) = self.killers.get(self.killer_index) else {
+                        self.stage = Stage::Quiets;
+                        self.quiets(board)
+                            .sort_by_cached_key(|r#move| history.get(r#move));
+                        continue;
+                    };
+                    self.killer_index += 1;
+
+                    if let Some(killer) = killer {
+                        if self.table_move != Some(killer) && self.quiets(board).contains(&killer) {
+                            return Some(killer);
+                        }
+                    }
+                }
+                Stage::Quiets => {
+                    let Some(r#move) = self.quiets(board).pop() else {
+                        self.stage = Stage::LosingCaptures;
+                        self.losing_captures.reverse();
+                        continue;
+                    };
+
+                    if !self.already_picked(&r#move) {
+                        return Some(r#move);
+                    }
+                }
+                Stage::LosingCaptures => {
+                    let Some(r#move) = self.losing_captures.pop() else {
+                        self.stage = Stage::Done;
+                        continue;
+                    };
+
+                    return Some(r#move);
+                }
+                Stage::Done => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::move_generation::legal_moves;
+
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+
+    fn find(board: &Board, text: &str) -> Move {
+        return legal_moves(board)
+            .into_iter()
+            .find(|r#move| r#move.to_string() == text)
+            .unwrap();
+    }
+
+    fn picked(board: &Board, mut picker: MovePicker, history: &History) -> Vec<Move> {
+        let mut moves: Vec<Move> = Vec::new();
+        while let Some(r#move) = picker.next(board, history) {
+            moves.push(r#move);
+        }
+
+        return moves;
+    }
+
+    #[test]
+    fn returns_every_legal_move_once() {
+        let board = Board::from_fen(KIWIPETE).unwrap();
+        let history = History::new();
+        let killers = [Some(find(&board, "a2a3")), Some(find(&board, "e1g1"))];
+        let picker = MovePicker::new(Some(find(&board, "e2a6")), killers);
+
+        let mut moves: Vec<String> = picked(&board, picker, &history)
+            .iter()
+            .map(|r#move| r#move.to_string())
+            .collect();
+        let mut expected: Vec<String> = legal_moves(&board)
+            .iter()
+            .map(|r#move| r#move.to_string())
+            .collect();
+        moves.sort();
+        expected.sort();
+
+        assert_eq!(expected, moves);
+    }
+
+    #[test]
+    fn capturing_killers_are_not_lost() {
+        // Killers come from sibling positions, where Bxa6 may have been quiet
+        let board = Board::from_fen(KIWIPETE).unwrap();
+        let killers = [Some(find(&board, "e2a6")), Some(find(&board, "a2a3"))];
+        let picker = MovePicker::new(None, killers);
+
+        let mut moves: Vec<String> = picked(&board, picker, &History::new())
+            .iter()
+            .map(|r#move| r#move.to_string())
+            .collect();
+        let mut expected: Vec<String> = legal_moves(&board)
+            .iter()
+            .map(|r#move| r#move.to_string())
+            .collect();
+        moves.sort();
+        expected.sort();
+
+        assert_eq!(expected, moves);
+    }
+
+    #[test]
+    fn stages_come_in_order() {
+        let board = Board::from_fen(KIWIPETE).unwrap();
+        let mut history = History::new();
+        history.update(&find(&board, "d5d6"), 500);
+
+        let killers = [Some(find(&board, "a2a3")), None];
+        let moves = picked(
+            &board,
+            MovePicker::new(Some(find(&board, "e1g1")), killers),
+            &history,
+        );
+        let text: Vec<String> = moves.iter().map(|r#move| r#move.to_string()).collect();
+
+        assert_eq!("e1g1", text[0]);
+        // Bxa6 wins a bishop, the pawn captures come after it
+        assert_eq!("e2a6", text[1]);
+
+        let killer = text.iter().position(|r#move| r#move == "a2a3").unwrap();
+        assert!(moves[1..killer]
+            .iter()
+            .all(|r#move| !is_quiet(&board, r#move)));
+        assert_eq!("d5d6", text[killer + 1]);
+
+        // Qxf6 gxf6 and Nxg6 hxg6 lose material and come after every quiet move
+        let losing = moves
+            .iter()
+            .position(|r#move| !is_quiet(&board, r#move) && !see_ge(&board, r#move, 0))
+            .unwrap();
+        assert!(moves[killer..losing]
+            .iter()
+            .all(|r#move| is_quiet(&board, r#move)));
+        assert!(moves[losing..]
+            .iter()
+            .all(|r#move| !see_ge(&board, r#move, 0)));
+        assert!(text[losing..].contains(&"f3f6".to_string()));
+        assert!(text[losing..].contains(&"e5g6".to_string()));
+    }
+
+    #[test]
+    fn moves_from_other_positions_are_skipped() {
+        // Neither e2e4 nor d7d5 is legal with the king in check
+        let board = Board::from_fen("4k3/8/8/8/8/8/8/r3K3 w - - 0 1").unwrap();
+        let other = Board::from_fen(crate::board::START_FEN).unwrap();
+        let killers = [Some(find(&other, "e2e4")), None];
+        let picker = MovePicker::new(Some(find(&other, "d2d4")), killers);
+
+        let moves = picked(&board, picker, &History::new());
+        assert_eq!(legal_moves(&board).len(), moves.len());
+        assert!(moves.iter().all(|r#move| r#move.piece == Pieces::Kings));
+    }
+
+    #[test]
+    fn history_stays_bounded() {
+        let board = Board::from_fen(KIWIPETE).unwrap();
+        let r#move = find(&board, "a2a3");
+        let mut history = History::new();
+
+        for _ in 0..1000 {
+            history.update(&r#move, 10_000);
+        }
+        assert!(history.get(&r#move) <= MAX_HISTORY);
+
+        history.update(&r#move, -MAX_HISTORY);
+        assert!(history.get(&r#move) < MAX_HISTORY / 2);
+    }
+}
@@ -0,0 +1,229 @@
+use crate::{
+    bitboard::{Bitboard, FILE_A, FILE_H, RANK8},
+    board::Board,
+    evaluation::Score,
+    piece::Pieces,
+};
+
+const DOUBLED: Score = Score::new(-10, -20);
+const ISOLATED: Score = Score::new(-8, -15);
+const BACKWARD: Score = Score::new(-6, -10);
+
+/// Indexed by rank from the pawn's own side, 0 being its first rank.
+const PASSED: [Score; 8] = [
+    Score::new(0, 0),
+    Score::new(5, 10),
+    Score::new(5, 15),
+    Score::new(12, 25),
+    Score::new(25, 45),
+    Score::new(45, 80),
+    Score::new(70, 120),
+    Score::new(0, 0),
+];
+
+/// For pawns defended by a pawn or standing next to one, by rank like `PASSED`.
+const CONNECTED: [Score; 8] = [
+    Score::new(0, 0),
+    Score::new(0, 0),
+    Score::new(4, 2),
+    Score::new(6, 4),
+    Score::new(10, 8),
+    Score::new(18, 15),
+    Score::new(30, 30),
+    Score::new(0, 0),
+];
+
+const DEFAULT_ENTRIES: usize = 1 << 14;
+
+pub fn file_mask(square: u32) -> Bitboard {
+    return FILE_A << (square % 8);
+}
+
+pub fn adjacent_files(square: u32) -> Bitboard {
+    let file = file_mask(square);
+
+    return ((file << 1) & !FILE_A) | ((file >> 1) & !FILE_H);
+}
+
+pub fn rank_mask(square: u32) -> Bitboard {
+    return RANK8 << (square - square % 8);
+}
+
+/// Every square on the ranks in front of the square, as seen from `white`'s side.
+pub fn forward_ranks(square: u32, white: bool) -> Bitboard {
+    let rank_start = square - square % 8;
+
+    return if white {
+        (1 << rank_start) - 1
+    } else {
+        Bitboard::MAX.checked_shl(rank_start + 8).unwrap_or(0)
+    };
+}
+
+pub fn pawn_attacks(pawns: Bitboard, white: bool) -> Bitboard {
+    return if white {
+        (pawns >> 7 & !FILE_A) | (pawns >> 9 & !FILE_H)
+    } else {
+        (pawns << 7 & !FILE_H) | (pawns << 9 & !FILE_A)
+    };
+}
+
+/// Pawn structure score of one side.
+fn side_score(own_pawns: Bitboard, enemy_pawns: Bitboard, white: bool) -> Score {
+    let mut score = Score::default();
+    let own_attacks = pawn_attacks(own_pawns, white);
+    let enemy_attacks = pawn_attacks(enemy_pawns, !white);
+
+    let mut pawns = own_pawns;
+    while pawns > 0 {
+        let square = pawns.trailing_zeros();
+        let forward = forward_ranks(square, white);
+        let file = file_mask(square);
+        let adjacent = adjacent_files(square);
+        let relative_rank = if white { 7 - square / 8 } else { square / 8 } as usize;
+
+        if enemy_pawns & (file | adjacent) & forward == 0 {
+            score += PASSED[relative_rank];
+        }
+
+        // Only the rear pawn of a doubled pair counts, so each extra pawn is penalised once
+        if own_pawns & file & forward > 0 {
+            score += DOUBLED;
+        }
+
+        if own_pawns & adjacent == 0 {
+            score += ISOLATED;
+        } else {
+            // Nothing can come up to support it and it can't advance safely either
+            // Shifted rather than indexed, so a pawn on its last rank has no stop square
+            let stop_square = if white {
+                (1 << square) >> 8
+            } else {
+                (1 << square) << 8
+            };
+            if own_pawns & adjacent & !forward == 0 && enemy_attacks & stop_square > 0 {
+                score += BACKWARD;
+            }
+        }
+
+        let phalanx = own_pawns & adjacent & rank_mask(square);
+        if phalanx > 0 || own_attacks & (1 << square) > 0 {
+            score += CONNECTED[relative_rank];
+        }
+
+        pawns &= pawns - 1;
+    }
+
+    return score;
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PawnEntry {
+    key: u64,
+    /// White's and black's pawn structure scores.
+    pub scores: [Score; 2],
+}
+
+/// Evaluates the pawn structure from scratch.
+pub fn evaluate_pawns(board: &Board) -> PawnEntry {
+    let pawns = board.pieces[Pieces::Pawns as usize];
+    let white_pawns = pawns & board.white_pieces;
+    let black_pawns = pawns & board.black_pieces;
+
+    return PawnEntry {
+        key: board.pawn_hash,
+        scores: [
+            side_score(white_pawns, black_pawns, true),
+            side_score(black_pawns, white_pawns, false),
+        ],
+    };
+}
+
+/// Pawn structure results by pawn key. The structure rarely changes between nodes, so
+/// most lookups hit. An empty entry has key 0 and no score, which is also the right
+/// answer for a position without pawns.
+pub struct PawnTable {
+    entries: Vec<PawnEntry>,
+}
+
+impl PawnTable {
+    pub fn new() -> Self {
+        PawnTable {
+            entries: vec![PawnEntry::default(); DEFAULT_ENTRIES],
+        }
+    }
+
+    pub fn get(&mut self, board: &Board) -> PawnEntry {
+        let index = board.pawn_hash as usize & (self.entries.len() - 1);
+
+        if self.entries[index].key != board.pawn_hash {
+            self.entries[index] = evaluate_pawns(board);
+        }
+
+        return self.entries[index];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scores(fen: &str) -> [Score; 2] {
+        return evaluate_pawns(&Board::from_fen(fen).unwrap()).scores;
+    }
+
+    #[test]
+    fn passed_pawns_grow_by_rank() {
+        let [white, black] = scores("4k3/8/1P6/8/8/8/6p1/4K3 w - - 0 1");
+        assert_eq!(PASSED[5] + ISOLATED, white);
+        assert_eq!(PASSED[6] + ISOLATED, black);
+
+        // A pawn on an adjacent file in front stops it from being passed
+        let [white, _] = scores("4k3/p7/8/1P6/8/8/8/4K3 w - - 0 1");
+        assert_eq!(ISOLATED, white);
+    }
+
+    #[test]
+    fn doubled_and_isolated() {
+        let [white, _] = scores("4k3/8/8/8/8/3P4/3P4/4K3 w - - 0 1");
+        assert_eq!(PASSED[1] + PASSED[2] + DOUBLED + ISOLATED + ISOLATED, white);
+    }
+
+    #[test]
+    fn backward_pawn() {
+        // d3 can't be supported by c4 and the black pawn on e5 guards d4
+        let [white, _] = scores("4k3/8/8/4p3/2P5/3P4/8/4K3 w - - 0 1");
+        let [white_without_e5, _] = scores("4k3/8/8/8/2P5/3P4/8/4K3 w - - 0 1");
+
+        // Without e5, d3 is a passed pawn instead
+        assert_eq!(BACKWARD, white - white_without_e5 + PASSED[2]);
+    }
+
+    #[test]
+    fn pawns_on_the_last_rank() {
+        // Not legal, but the evaluation must not overflow past the edge of the board
+        let [white, _] = scores("PP2k3/8/8/8/8/8/8/4K3 w - - 0 1");
+        assert_eq!(PASSED[7] + PASSED[7], white);
+
+        let [_, black] = scores("4k3/8/8/8/8/8/8/4K1pp w - - 0 1");
+        assert_eq!(PASSED[7] + PASSED[7], black);
+    }
+
+    #[test]
+    fn connected_pawns() {
+        // c4 is defended by d3, e4 and f4 stand next to each other
+        let [white, _] = scores("4k3/pppppppp/8/8/2P1PP2/3P4/8/4K3 w - - 0 1");
+        assert_eq!(CONNECTED[3] + CONNECTED[3] + CONNECTED[3], white);
+    }
+
+    #[test]
+    fn cached_by_pawn_key() {
+        let mut table = PawnTable::new();
+        let board = Board::from_fen("4k3/8/8/4p3/2P5/3P4/8/4K3 w - - 0 1").unwrap();
+        let moved_king = Board::from_fen("3k4/8/8/4p3/2P5/3P4/8/3K4 w - - 0 1").unwrap();
+
+        assert_eq!(evaluate_pawns(&board), table.get(&board));
+        assert_eq!(board.pawn_hash, moved_king.pawn_hash);
+        assert_eq!(table.get(&board), table.get(&moved_king));
+    }
+}
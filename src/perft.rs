@@ -0,0 +1,119 @@
+use crate::{board::Board, move_generation::legal_moves, r#move::Move};
+
+/// Counts the leaf nodes of the legal move tree `depth` plies deep.
+pub fn perft(board: &mut Board, depth: u32) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+
+    let moves = legal_moves(board);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+
+    let mut nodes = 0;
+    for r#move in moves {
+        let undo = board.make_move(&r#move);
+        nodes += perft(board, depth - 1);
+        board.unmake_move(&r#move, undo);
+    }
+
+    return nodes;
+}
+
+/// Perft split up by root move, to narrow down which move a wrong count comes from.
+pub fn perft_divide(board: &mut Board, depth: u32) -> Vec<(Move, u64)> {
+    let mut divide = Vec::new();
+
+    if depth == 0 {
+        return divide;
+    }
+
+    for r#move in legal_moves(board) {
+        let undo = board.make_move(&r#move);
+        divide.push((r#move, perft(board, depth - 1)));
+        board.unmake_move(&r#move, undo);
+    }
+
+    return divide;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::START_FEN;
+
+    fn assert_perft(fen: &str, expected: &[u64]) {
+        let mut board = Board::from_fen(fen).unwrap();
+
+        for (depth, &nodes) in expected.iter().enumerate() {
+            assert_eq!(
+                nodes,
+                perft(&mut board, depth as u32 + 1),
+                "{fen} at depth {}",
+                depth + 1
+            );
+        }
+    }
+
+    #[test]
+    fn perft_start_position() {
+        assert_perft(START_FEN, &[20, 400, 8902, 197281]);
+    }
+
+    #[test]
+    fn perft_kiwipete() {
+        assert_perft(
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            &[48, 2039, 97862],
+        );
+    }
+
+    #[test]
+    fn perft_position_3() {
+        assert_perft(
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            &[14, 191, 2812, 43238],
+        );
+    }
+
+    #[test]
+    fn perft_position_4() {
+        assert_perft(
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            &[6, 264, 9467],
+        );
+        assert_perft(
+            "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1",
+            &[6, 264, 9467],
+        );
+    }
+
+    #[test]
+    fn perft_position_5() {
+        assert_perft(
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            &[44, 1486, 62379],
+        );
+    }
+
+    #[test]
+    fn perft_position_6() {
+        assert_perft(
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+            &[46, 2079, 89890],
+        );
+    }
+
+    #[test]
+    fn divide_adds_up_to_perft() {
+        let mut board = Board::from_fen(START_FEN).unwrap();
+        let divide = perft_divide(&mut board, 3);
+
+        assert_eq!(20, divide.len());
+        assert_eq!(8902, divide.iter().map(|(_, nodes)| nodes).sum::<u64>());
+        assert!(divide
+            .iter()
+            .any(|(r#move, nodes)| r#move.to_string() == "e2e4" && *nodes == 600));
+    }
+}
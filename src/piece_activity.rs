@@ -0,0 +1,193 @@
+use crate::{
+    bitboard::{Bitboard, RANK2, RANK7},
+    board::Board,
+    evaluation::Score,
+    move_generation::piece_attacks,
+    pawn_structure::{adjacent_files, file_mask, forward_ranks, pawn_attacks},
+    piece::Pieces,
+};
+
+/// Per mobility square, and the number of squares that is worth nothing. Fewer
+/// squares than that is a penalty.
+fn mobility_weight(piece: Pieces) -> (Score, i32) {
+    return match piece {
+        Pieces::Knights => (Score::new(4, 4), 4),
+        Pieces::Bishops => (Score::new(5, 5), 6),
+        Pieces::Rooks => (Score::new(2, 4), 7),
+        Pieces::Queens => (Score::new(1, 2), 13),
+        Pieces::Pawns | Pieces::Kings => (Score::new(0, 0), 0),
+    };
+}
+
+const ROOK_OPEN_FILE: Score = Score::new(25, 10);
+const ROOK_HALF_OPEN_FILE: Score = Score::new(12, 6);
+const ROOK_ON_SEVENTH: Score = Score::new(20, 30);
+const BISHOP_PAIR: Score = Score::new(30, 50);
+const KNIGHT_OUTPOST: Score = Score::new(25, 12);
+
+struct Sides {
+    own_pieces: Bitboard,
+    own_pawns: Bitboard,
+    enemy_pawns: Bitboard,
+}
+
+fn sides(board: &Board, white: bool) -> Sides {
+    let pawns = board.pieces[Pieces::Pawns as usize];
+    let (own_pieces, enemy_pieces) = if white {
+        (board.white_pieces, board.black_pieces)
+    } else {
+        (board.black_pieces, board.white_pieces)
+    };
+
+    return Sides {
+        own_pieces,
+        own_pawns: pawns & own_pieces,
+        enemy_pawns: pawns & enemy_pieces,
+    };
+}
+
+/// Squares each knight, bishop, rook and queen can go to, leaving out squares enemy
+/// pawns attack since a piece can't stay there.
+pub fn mobility(board: &Board, white: bool) -> Score {
+    let sides = sides(board, white);
+    let occupied = board.white_pieces | board.black_pieces;
+    let available = !sides.own_pieces & !pawn_attacks(sides.enemy_pawns, !white);
+    let mut score = Score::default();
+
+    for piece in [
+        Pieces::Knights,
+        Pieces::Bishops,
+        Pieces::Rooks,
+        Pieces::Queens,
+    ] {
+        let (weight, baseline) = mobility_weight(piece);
+        let mut pieces = board.pieces[piece as usize] & sides.own_pieces;
+
+        while pieces > 0 {
+            let square = pieces.trailing_zeros();
+            let squares = (piece_attacks(piece, square, occupied) & available).count_ones() as i32;
+            score += Score::new(
+                weight.mg * (squares - baseline),
+                weight.eg * (squares - baseline),
+            );
+
+            pieces &= pieces - 1;
+        }
+    }
+
+    return score;
+}
+
+/// Rooks on open files and the seventh rank, the bishop pair and knight outposts.
+pub fn activity(board: &Board, white: bool) -> Score {
+    let sides = sides(board, white);
+    let mut score = Score::default();
+
+    let bishops = board.pieces[Pieces::Bishops as usize] & sides.own_pieces;
+    if bishops.count_ones() >= 2 {
+        score += BISHOP_PAIR;
+    }
+
+    let enemy_king = board.pieces[Pieces::Kings as usize] & !sides.own_pieces;
+    let (seventh_rank, eighth_rank) = if white {
+        (RANK7, RANK7 >> 8)
+    } else {
+        (RANK2, RANK2 << 8)
+    };
+
+    let mut rooks = board.pieces[Pieces::Rooks as usize] & sides.own_pieces;
+    while rooks > 0 {
+        let square = rooks.trailing_zeros();
+        let file = file_mask(square);
+
+        if (sides.own_pawns | sides.enemy_pawns) & file == 0 {
+            score += ROOK_OPEN_FILE;
+        } else if sides.own_pawns & file == 0 {
+            score += ROOK_HALF_OPEN_FILE;
+        }
+
+        // Only worth it with pawns to eat there or the king cut off on its back rank
+        if seventh_rank & (1 << square) > 0
+            && (sides.enemy_pawns & seventh_rank > 0 || enemy_king & eighth_rank > 0)
+        {
+            score += ROOK_ON_SEVENTH;
+        }
+
+        rooks &= rooks - 1;
+    }
+
+    // A knight in enemy territory, defended by a pawn, that no enemy pawn can chase away
+    let own_pawn_attacks = pawn_attacks(sides.own_pawns, white);
+    let mut knights = board.pieces[Pieces::Knights as usize] & sides.own_pieces;
+    while knights > 0 {
+        let square = knights.trailing_zeros();
+        let relative_rank = if white { 7 - square / 8 } else { square / 8 };
+
+        if (3..=5).contains(&relative_rank)
+            && own_pawn_attacks & (1 << square) > 0
+            && sides.enemy_pawns & adjacent_files(square) & forward_ranks(square, white) == 0
+        {
+            score += KNIGHT_OUTPOST;
+        }
+
+        knights &= knights - 1;
+    }
+
+    return score;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn board(fen: &str) -> Board {
+        return Board::from_fen(fen).unwrap();
+    }
+
+    #[test]
+    fn mobility_counts_reachable_squares() {
+        // A bishop in the centre reaches 13 squares, one in the corner behind its own
+        // pawn reaches none
+        let centre = mobility(&board("4k3/8/8/8/3B4/8/8/4K3 w - - 0 1"), true);
+        assert_eq!(Score::new(5 * (13 - 6), 5 * (13 - 6)), centre);
+
+        let blocked = mobility(&board("4k3/8/8/8/8/8/1P6/B3K3 w - - 0 1"), true);
+        assert_eq!(Score::new(5 * -6, 5 * -6), blocked);
+    }
+
+    #[test]
+    fn mobility_avoids_enemy_pawn_attacks() {
+        // The black pawns on c5 and e5 cover b4 and f4 and the king stands on e1, leaving
+        // 5 of the knight's 8 squares, one more than the baseline of 4
+        let knight = mobility(&board("4k3/8/8/2p1p3/8/3N4/8/4K3 w - - 0 1"), true);
+        assert_eq!(Score::new(4, 4), knight);
+    }
+
+    #[test]
+    fn rook_files_and_seventh_rank() {
+        let open = activity(&board("4k3/8/8/8/8/8/8/R3K3 w - - 0 1"), true);
+        assert_eq!(ROOK_OPEN_FILE, open);
+
+        let half_open = activity(&board("4k3/p7/8/8/8/8/8/R3K3 w - - 0 1"), true);
+        assert_eq!(ROOK_HALF_OPEN_FILE, half_open);
+
+        let seventh = activity(&board("4k3/R7/8/8/8/8/8/4K3 w - - 0 1"), true);
+        assert_eq!(ROOK_OPEN_FILE + ROOK_ON_SEVENTH, seventh);
+
+        let black_seventh = activity(&board("4k3/8/8/8/8/8/r7/4K3 b - - 0 1"), false);
+        assert_eq!(seventh, black_seventh);
+    }
+
+    #[test]
+    fn bishop_pair_and_outposts() {
+        let pair = activity(&board("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1"), true);
+        assert_eq!(BISHOP_PAIR, pair);
+
+        let outpost = activity(&board("4k3/8/8/3N4/4P3/8/8/4K3 w - - 0 1"), true);
+        assert_eq!(KNIGHT_OUTPOST, outpost);
+
+        // A pawn on c7 can still kick the knight
+        let kicked = activity(&board("4k3/2p5/8/3N4/4P3/8/8/4K3 w - - 0 1"), true);
+        assert_eq!(Score::default(), kicked);
+    }
+}
@@ -0,0 +1,909 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::{
+    board::Board,
+    evaluation::evaluate_cached,
+    move_generation::{gives_check, is_square_attacked, legal_captures, legal_moves},
+    move_picker::{is_quiet, mvv_lva, History, Killers, MovePicker},
+    pawn_structure::PawnTable,
+    piece::Pieces,
+    r#move::Move,
+    see::see_ge,
+    time_manager::TimeManager,
+    transposition_table::{Bound, TranspositionTable},
+};
+
+pub const INFINITY: i32 = 32_000;
+/// Score of being checkmated right now. Mates further away score `MATE - ply`.
+pub const MATE: i32 = 31_000;
+pub const MAX_PLY: u32 = 128;
+
+/// How often, in nodes, the stop flag and the limits are looked at.
+const CHECK_INTERVAL: u64 = 1024;
+
+/// Lazy SMP helper threads skip some depths so they don't all search the same one.
+/// Helper `n` uses entry `(n - 1) % 20` and skips a depth when
+/// `(depth + phase) / size` is odd, so they spread out over neighbouring depths.
+const SKIP_SIZE: [u32; 20] = [1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 4, 4, 4, 4, 4, 4, 4, 4];
+const SKIP_PHASE: [u32; 20] = [0, 1, 0, 1, 2, 3, 0, 1, 2, 3, 4, 5, 0, 1, 2, 3, 4, 5, 6, 7];
+
+/// Reverse futility pruning applies up to this depth, with this margin per ply.
+const REVERSE_FUTILITY_DEPTH: u32 = 6;
+const REVERSE_FUTILITY_MARGIN: i32 = 80;
+
+/// Futility pruning applies up to this depth, with this margin per ply.
+const FUTILITY_DEPTH: u32 = 3;
+const FUTILITY_MARGIN: i32 = 120;
+
+const NULL_MOVE_DEPTH: u32 = 3;
+
+/// Aspiration windows are used from this depth on, starting this far on either side
+/// of the previous score and doubling every time the score falls outside.
+const ASPIRATION_DEPTH: u32 = 4;
+const ASPIRATION_WINDOW: i32 = 25;
+
+/// Late move reductions start at this depth, after this many moves.
+const REDUCTION_DEPTH: u32 = 3;
+const REDUCTION_MOVES: usize = 3;
+
+/// Switches for the selective parts of the search, so their contribution can be
+/// measured by playing against a version without them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pruning {
+    /// Skip a turn and cut off if the opponent still can't reach beta.
+    pub null_move: bool,
+    /// Search quiet moves late in the move order less deep.
+    pub late_move_reductions: bool,
+    /// Cut off when the static score is far above beta near the leaves.
+    pub reverse_futility: bool,
+    /// Skip quiet moves that can't bring the static score up to alpha near the leaves.
+    pub futility: bool,
+}
+
+impl Pruning {
+    pub const NONE: Pruning = Pruning {
+        null_move: false,
+        late_move_reductions: false,
+        reverse_futility: false,
+        futility: false,
+    };
+}
+
+impl Default for Pruning {
+    fn default() -> Self {
+        Pruning {
+            null_move: true,
+            late_move_reductions: true,
+            reverse_futility: true,
+            futility: true,
+        }
+    }
+}
+
+static REDUCTIONS: OnceLock<[[u32; 64]; 64]> = OnceLock::new();
+
+/// Plies to reduce the `move_number`th move by at `depth`, growing with the logarithm
+/// of both.
+fn reduction(depth: u32, move_number: usize) -> u32 {
+    let reductions = REDUCTIONS.get_or_init(|| {
+        let mut reductions = [[0; 64]; 64];
+
+        for (depth, row) in reductions.iter_mut().enumerate().skip(1) {
+            for (move_number, reduction) in row.iter_mut().enumerate().skip(1) {
+                let ln_depth = (depth as f64).ln();
+                let ln_moves = (move_number as f64).ln();
+                *reduction = (0.75 + ln_depth * ln_moves / 2.25) as u32;
+            }
+        }
+
+        reductions
+    });
+
+    return reductions[(depth as usize).min(63)][move_number.min(63)];
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Limits {
+    pub depth: Option<u32>,
+    pub nodes: Option<u64>,
+    pub deadline: Option<Instant>,
+    /// Decides after each iteration whether to start another one when playing on a
+    /// clock. Its hard limit goes in `deadline`.
+    pub time_manager: Option<TimeManager>,
+}
+
+/// Outcome of an iteration of iterative deepening. An iteration is complete once the
+/// score is exact; while the aspiration window gets widened the score is a bound.
+#[derive(Clone, Debug)]
+pub struct SearchResult {
+    pub depth: u32,
+    pub best_move: Move,
+    pub score: i32,
+    pub bound: Bound,
+    pub pv: Vec<Move>,
+    pub nodes: u64,
+    pub elapsed: Duration,
+    /// Transposition table usage in per-mille.
+    pub hashfull: u32,
+}
+
+pub fn is_mate_score(score: i32) -> bool {
+    return score.abs() > MATE - MAX_PLY as i32;
+}
+
+fn in_check(board: &Board) -> bool {
+    let white_turn = board.game_state.white_turn;
+    let own_pieces = if white_turn {
+        board.white_pieces
+    } else {
+        board.black_pieces
+    };
+    let king = board.pieces[Pieces::Kings as usize] & own_pieces;
+
+    return king > 0 && is_square_attacked(board, king.trailing_zeros(), !white_turn);
+}
+
+/// Whether the side to move has anything besides pawns and its king. Without, being
+/// forced to move is often what loses, so passing says nothing about the position.
+fn has_non_pawn_material(board: &Board) -> bool {
+    let own_pieces = if board.game_state.white_turn {
+        board.white_pieces
+    } else {
+        board.black_pieces
+    };
+    let pieces = board.pieces[Pieces::Knights as usize]
+        | board.pieces[Pieces::Bishops as usize]
+        | board.pieces[Pieces::Rooks as usize]
+        | board.pieces[Pieces::Queens as usize];
+
+    return pieces & own_pieces > 0;
+}
+
+pub struct Searcher<'a> {
+    board: Board,
+    table: &'a TranspositionTable,
+    pawn_table: PawnTable,
+    killers: Killers,
+    history: History,
+    limits: Limits,
+    pruning: Pruning,
+    /// Set while searching the reply to a null move, so two never follow each other.
+    after_null_move: bool,
+    stop: Arc<AtomicBool>,
+    stopped: bool,
+    nodes: u64,
+    /// Nodes of all threads searching together, added to in batches of
+    /// `CHECK_INTERVAL`.
+    shared_nodes: Arc<AtomicU64>,
+    /// 0 for the main thread, helper threads count up from 1.
+    thread: usize,
+    start: Instant,
+}
+
+impl<'a> Searcher<'a> {
+    pub fn new(
+        board: &Board,
+        limits: Limits,
+        stop: Arc<AtomicBool>,
+        table: &'a TranspositionTable,
+    ) -> Self {
+        Searcher {
+            board: board.clone(),
+            table,
+            pawn_table: PawnTable::new(),
+            killers: Killers::new(MAX_PLY as usize),
+            history: History::new(),
+            limits,
+            pruning: Pruning::default(),
+            after_null_move: false,
+            stop,
+            stopped: false,
+            nodes: 0,
+            shared_nodes: Arc::new(AtomicU64::new(0)),
+            thread: 0,
+            start: Instant::now(),
+        }
+    }
+
+    pub fn set_pruning(&mut self, pruning: Pruning) {
+        self.pruning = pruning;
+    }
+
+    /// Makes this searcher thread `thread` of a Lazy SMP search, adding its nodes to
+    /// the count all threads share.
+    pub fn set_thread(&mut self, thread: usize, shared_nodes: Arc<AtomicU64>) {
+        self.thread = thread;
+        self.shared_nodes = shared_nodes;
+    }
+
+    /// Nodes searched by all threads. Each thread adds its nodes every
+    /// `CHECK_INTERVAL`, so the count of the others lags behind a little.
+    fn total_nodes(&self) -> u64 {
+        return self.shared_nodes.load(Ordering::Relaxed) + self.nodes % CHECK_INTERVAL;
+    }
+
+    fn skips_depth(&self, depth: u32) -> bool {
+        if self.thread == 0 {
+            return false;
+        }
+
+        let index = (self.thread - 1) % SKIP_SIZE.len();
+        return (depth + SKIP_PHASE[index]) / SKIP_SIZE[index] % 2 == 1;
+    }
+
+    fn limits_reached(&self) -> bool {
+        let out_of_nodes = self
+            .limits
+            .nodes
+            .is_some_and(|nodes| self.total_nodes() >= nodes);
+        let out_of_time = self
+            .limits
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline);
+
+        return out_of_nodes || out_of_time || self.stop.load(Ordering::Relaxed);
+    }
+
+    fn should_stop(&mut self) -> bool {
+        if !self.stopped && self.nodes.is_multiple_of(CHECK_INTERVAL) {
+            self.shared_nodes
+                .fetch_add(CHECK_INTERVAL, Ordering::Relaxed);
+            self.stopped = self.limits_reached();
+        }
+
+        return self.stopped;
+    }
+
+    /// Searches captures and promotions until the position is quiet, so the horizon
+    /// never falls in the middle of an exchange. The side to move may always stand pat
+    /// on the static score instead, unless it is in check.
+    fn quiescence(&mut self, ply: u32, mut alpha: i32, beta: i32) -> i32 {
+        self.nodes += 1;
+
+        if self.should_stop() {
+            return 0;
+        }
+
+        if ply >= MAX_PLY {
+            return evaluate_cached(&self.board, &mut self.pawn_table);
+        }
+
+        let in_check = in_check(&self.board);
+        let mut best_score = -INFINITY;
+
+        if !in_check {
+            best_score = evaluate_cached(&self.board, &mut self.pawn_table);
+
+            if best_score >= beta {
+                return best_score;
+            }
+            alpha = alpha.max(best_score);
+        }
+
+        // In check every evasion has to be looked at, not just the captures
+        let mut moves = if in_check {
+            legal_moves(&self.board)
+        } else {
+            legal_captures(&self.board)
+        };
+
+        if in_check && moves.is_empty() {
+            return -MATE + ply as i32;
+        }
+
+        moves.sort_by_cached_key(|r#move| -mvv_lva(&self.board, r#move));
+
+        for r#move in moves {
+            // Captures that lose material can't raise the score above standing pat
+            if !in_check && !see_ge(&self.board, &r#move, 0) {
+                continue;
+            }
+
+            let undo = self.board.make_move(&r#move);
+            let score = -self.quiescence(ply + 1, -beta, -alpha);
+            self.board.unmake_move(&r#move, undo);
+
+            if self.stopped {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+                alpha = alpha.max(score);
+            }
+
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        return best_score;
+    }
+
+    /// Negamax with alpha-beta pruning. Fills `pv` with the best line found below
+    /// this node. Transposition table scores only cut off below the root and when they
+    /// fall outside the window, so the principal variation stays complete.
+    fn negamax(
+        &mut self,
+        depth: u32,
+        ply: u32,
+        mut alpha: i32,
+        beta: i32,
+        pv: &mut Vec<Move>,
+    ) -> i32 {
+        pv.clear();
+        let after_null_move = std::mem::take(&mut self.after_null_move);
+
+        if depth == 0 {
+            return self.quiescence(ply, alpha, beta);
+        }
+
+        self.nodes += 1;
+
+        if self.should_stop() {
+            return 0;
+        }
+
+        let checked = in_check(&self.board);
+
+        // Being checkmated still beats the fifty-move rule
+        if self.board.game_state.half_moves >= 100 {
+            return if checked && legal_moves(&self.board).is_empty() {
+                -MATE + ply as i32
+            } else {
+                0
+            };
+        }
+
+        if ply >= MAX_PLY {
+            return evaluate_cached(&self.board, &mut self.pawn_table);
+        }
+
+        let hash = self.board.hash;
+        let entry = self.table.probe(hash);
+
+        if let Some(entry) = entry {
+            let score = entry.score(ply);
+
+            if ply > 0 && entry.depth as u32 >= depth {
+                let cutoff = match entry.bound {
+                    Bound::Exact => score <= alpha || score >= beta,
+                    Bound::Lower => score >= beta,
+                    Bound::Upper => score <= alpha,
+                };
+
+                if cutoff {
+                    return score;
+                }
+            }
+        }
+
+        // Only zero-window searches are pruned, so the principal variation and mate
+        // scores are never left to the static evaluation
+        let pv_node = beta - alpha > 1;
+        let prune = !pv_node && !checked && !is_mate_score(beta);
+        let static_eval = if prune {
+            evaluate_cached(&self.board, &mut self.pawn_table)
+        } else {
+            -INFINITY
+        };
+
+        if prune
+            && self.pruning.reverse_futility
+            && depth <= REVERSE_FUTILITY_DEPTH
+            && static_eval - REVERSE_FUTILITY_MARGIN * depth as i32 >= beta
+        {
+            return static_eval;
+        }
+
+        if prune
+            && self.pruning.null_move
+            && !after_null_move
+            && depth >= NULL_MOVE_DEPTH
+            && static_eval >= beta
+            && has_non_pawn_material(&self.board)
+        {
+            let reduction = 3 + depth / 4;
+            let undo = self.board.make_null_move();
+            self.after_null_move = true;
+            let score = -self.negamax(
+                depth.saturating_sub(1 + reduction),
+                ply + 1,
+                -beta,
+                -beta + 1,
+                &mut Vec::new(),
+            );
+            self.after_null_move = false;
+            self.board.unmake_null_move(undo);
+
+            if self.stopped {
+                return 0;
+            }
+
+            // Unproven mates from a null move aren't trusted
+            if score >= beta {
+                return if is_mate_score(score) { beta } else { score };
+            }
+        }
+
+        let futility = prune
+            && self.pruning.futility
+            && depth <= FUTILITY_DEPTH
+            && static_eval + FUTILITY_MARGIN * (depth as i32) <= alpha;
+
+        let table_move = entry.and_then(|entry| entry.best_move);
+        let mut picker = MovePicker::new(table_move, self.killers.get(ply));
+
+        let original_alpha = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move: Option<Move> = None;
+        let mut child_pv: Vec<Move> = Vec::new();
+        let mut moves_searched = 0;
+        let mut quiets_searched: Vec<Move> = Vec::new();
+
+        while let Some(r#move) = picker.next(&self.board, &self.history) {
+            let quiet = is_quiet(&self.board, &r#move);
+
+            // Checks that don't lose material are searched one ply deeper
+            let gives_check = gives_check(&self.board, &r#move);
+            let extension = if gives_check && see_ge(&self.board, &r#move, 0) {
+                1
+            } else {
+                0
+            };
+            let undo = self.board.make_move(&r#move);
+
+            let new_depth = depth - 1 + extension;
+
+            // Quiet moves are hopeless this close to the leaves when even a good
+            // margin on top of the static score stays below alpha
+            if futility && quiet && !gives_check && moves_searched > 0 {
+                self.board.unmake_move(&r#move, undo);
+                continue;
+            }
+
+            // Root moves all get a full search so none of them is missed
+            let reduction = if self.pruning.late_move_reductions
+                && ply > 0
+                && depth >= REDUCTION_DEPTH
+                && moves_searched >= REDUCTION_MOVES
+                && quiet
+                && !checked
+                && !gives_check
+            {
+                reduction(depth, moves_searched + 1)
+                    .saturating_sub(pv_node as u32)
+                    .min(new_depth - 1)
+            } else {
+                0
+            };
+
+            // Only the first move gets the full window. The others are expected to fail
+            // low on a zero window around alpha; one that doesn't is searched again at
+            // full depth, then with the full window if it may be the new best move.
+            let score = if moves_searched == 0 {
+                -self.negamax(new_depth, ply + 1, -beta, -alpha, &mut child_pv)
+            } else {
+                let mut score = -self.negamax(
+                    new_depth - reduction,
+                    ply + 1,
+                    -alpha - 1,
+                    -alpha,
+                    &mut child_pv,
+                );
+                if score > alpha && reduction > 0 {
+                    score = -self.negamax(new_depth, ply + 1, -alpha - 1, -alpha, &mut child_pv);
+                }
+                if score > alpha && score < beta {
+                    score = -self.negamax(new_depth, ply + 1, -beta, -alpha, &mut child_pv);
+                }
+                score
+            };
+
+            self.board.unmake_move(&r#move, undo);
+            moves_searched += 1;
+
+            if self.stopped {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_move = Some(r#move);
+
+                if score > alpha {
+                    alpha = score;
+
+                    pv.clear();
+                    pv.push(r#move);
+                    pv.extend_from_slice(&child_pv);
+                }
+            }
+
+            if alpha >= beta {
+                // Quiet refutations are remembered for move ordering, the quiet moves
+                // tried before it lose some of their history
+                if quiet {
+                    let bonus = (depth * depth) as i32;
+                    self.killers.store(ply, r#move);
+                    self.history.update(&r#move, bonus);
+                    for tried in &quiets_searched {
+                        self.history.update(tried, -bonus);
+                    }
+                }
+                break;
+            }
+
+            if quiet {
+                quiets_searched.push(r#move);
+            }
+        }
+
+        if moves_searched == 0 {
+            return if checked { -MATE + ply as i32 } else { 0 };
+        }
+
+        let bound = if best_score >= beta {
+            Bound::Lower
+        } else if best_score > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        // A fail low doesn't tell which move was best
+        let best_move = if bound == Bound::Upper {
+            None
+        } else {
+            best_move
+        };
+        self.table
+            .store(hash, depth, ply, best_score, bound, best_move);
+
+        return best_score;
+    }
+
+    /// Searches the root at `depth` with a window around the previous score. When the
+    /// score falls outside the window, it is reported as a bound and the search is
+    /// repeated with the window widened on that side. Returns `None` when stopped or
+    /// without legal moves.
+    fn aspiration_search(
+        &mut self,
+        depth: u32,
+        previous: Option<&SearchResult>,
+        report: &mut impl FnMut(&SearchResult),
+    ) -> Option<SearchResult> {
+        let mut window = ASPIRATION_WINDOW;
+        let (mut alpha, mut beta) = match previous {
+            Some(previous) if depth >= ASPIRATION_DEPTH && !is_mate_score(previous.score) => {
+                (previous.score - window, previous.score + window)
+            }
+            _ => (-INFINITY, INFINITY),
+        };
+
+        loop {
+            let mut pv: Vec<Move> = Vec::new();
+            let score = self.negamax(depth, 0, alpha, beta, &mut pv);
+
+            if self.stopped {
+                return None;
+            }
+
+            let bound = if score <= alpha {
+                Bound::Upper
+            } else if score >= beta {
+                Bound::Lower
+            } else {
+                Bound::Exact
+            };
+
+            // Failing low finds no best move, so the previous line goes with the bound
+            if pv.is_empty() {
+                pv = previous?.pv.clone();
+            }
+
+            let result = SearchResult {
+                depth,
+                best_move: pv[0],
+                score,
+                bound,
+                pv,
+                nodes: self.total_nodes(),
+                elapsed: self.start.elapsed(),
+                hashfull: self.table.hashfull(),
+            };
+
+            match bound {
+                Bound::Exact => return Some(result),
+                Bound::Upper => {
+                    beta = (alpha + beta) / 2;
+                    alpha = (score - window).max(-INFINITY);
+                }
+                Bound::Lower => beta = (score + window).min(INFINITY),
+            }
+
+            report(&result);
+            window *= 2;
+        }
+    }
+
+    /// Iterative deepening up to the depth limit or until stopped. `report` is called
+    /// after every completed depth and for every bound found on the way; the results
+    /// of all completed depths are returned. The table isn't aged here, since several
+    /// threads may be searching with it; see `TranspositionTable::new_search`.
+    pub fn search(&mut self, mut report: impl FnMut(&SearchResult)) -> Vec<SearchResult> {
+        let mut results: Vec<SearchResult> = Vec::new();
+        let max_depth = self.limits.depth.unwrap_or(MAX_PLY).min(MAX_PLY);
+
+        self.start = Instant::now();
+
+        for depth in 1..=max_depth {
+            if self.limits_reached() {
+                break;
+            }
+
+            // Depth 1 is cheap and gives every thread a result
+            if depth > 1 && self.skips_depth(depth) {
+                continue;
+            }
+
+            let Some(result) = self.aspiration_search(depth, results.last(), &mut report) else {
+                break;
+            };
+            let score = result.score;
+            let best_move = result.best_move;
+            report(&result);
+            results.push(result);
+
+            if let Some(time_manager) = &mut self.limits.time_manager {
+                if time_manager.iteration_done(best_move, score) {
+                    break;
+                }
+            }
+
+            // Nothing will change a forced mate found at this depth
+            if is_mate_score(score) && MATE - score.abs() <= depth as i32 {
+                break;
+            }
+        }
+
+        return results;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{board::START_FEN, evaluation::evaluate};
+
+    fn search_depth(fen: &str, depth: u32) -> Vec<SearchResult> {
+        let board = Board::from_fen(fen).unwrap();
+        let limits = Limits {
+            depth: Some(depth),
+            ..Limits::default()
+        };
+        let table = TranspositionTable::new(1);
+        let mut searcher = Searcher::new(&board, limits, Arc::new(AtomicBool::new(false)), &table);
+
+        return searcher.search(|_| ());
+    }
+
+    #[test]
+    fn finds_mate_in_one() {
+        let results = search_depth("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1", 3);
+        let result = results.last().unwrap();
+
+        assert_eq!("a1a8", result.best_move.to_string());
+        assert_eq!(MATE - 1, result.score);
+    }
+
+    #[test]
+    fn finds_mate_in_two() {
+        let results = search_depth("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1", 4);
+        let result = results.last().unwrap();
+
+        assert_eq!("a1a6", result.best_move.to_string());
+        assert_eq!(MATE - 3, result.score);
+        assert_eq!(3, result.pv.len());
+    }
+
+    /// Score of the position searched to `depth`, with quiescence at depth 0.
+    fn search_score(fen: &str, depth: u32) -> i32 {
+        let board = Board::from_fen(fen).unwrap();
+        let table = TranspositionTable::new(1);
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut searcher = Searcher::new(&board, Limits::default(), stop, &table);
+
+        return searcher.negamax(depth, 0, -INFINITY, INFINITY, &mut Vec::new());
+    }
+
+    #[test]
+    fn scores_mate_and_stalemate() {
+        assert_eq!(0, search_score("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", 3));
+        assert_eq!(-MATE, search_score("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1", 3));
+
+        // Qg7 mates while Qg6 would stalemate
+        let results = search_depth("7k/5Q2/5K2/8/8/8/8/8 w - - 0 1", 2);
+        assert_eq!(MATE - 1, results.last().unwrap().score);
+    }
+
+    #[test]
+    fn quiescence_resolves_exchanges() {
+        // Taking the defended pawn with the queen loses her, so white stands pat
+        let fen = "4k3/8/2p5/3p4/8/8/3Q4/4K3 w - - 0 1";
+        let static_score = evaluate(&Board::from_fen(fen).unwrap());
+        assert_eq!(static_score, search_score(fen, 0));
+
+        // An undefended pawn is simply won
+        let fen = "4k3/8/8/3p4/8/8/3Q4/4K3 w - - 0 1";
+        let static_score = evaluate(&Board::from_fen(fen).unwrap());
+        assert!(search_score(fen, 0) > static_score + 50);
+
+        // A depth 1 search no longer grabs the defended pawn
+        let results = search_depth("4k3/8/2p5/3p4/8/8/3Q4/4K3 w - - 0 1", 1);
+        assert_ne!("d2d5", results[0].best_move.to_string());
+    }
+
+    #[test]
+    fn table_keeps_results_between_searches() {
+        let board = Board::from_fen(START_FEN).unwrap();
+        let table = TranspositionTable::new(1);
+        let stop = Arc::new(AtomicBool::new(false));
+        let limits = Limits {
+            depth: Some(4),
+            ..Limits::default()
+        };
+
+        let mut searcher = Searcher::new(&board, limits.clone(), Arc::clone(&stop), &table);
+        let first = searcher.search(|_| ()).pop().unwrap();
+        let mut searcher = Searcher::new(&board, limits, stop, &table);
+        let second = searcher.search(|_| ()).pop().unwrap();
+
+        assert!(second.nodes < first.nodes);
+        assert_eq!(first.score, second.score);
+        assert!(first.hashfull > 0);
+        assert_eq!(4, second.pv.len());
+    }
+
+    #[test]
+    fn reports_every_completed_depth() {
+        let results = search_depth(START_FEN, 3);
+
+        assert_eq!(
+            vec![1, 2, 3],
+            results.iter().map(|r| r.depth).collect::<Vec<u32>>()
+        );
+        for result in &results {
+            assert_eq!(result.best_move, result.pv[0]);
+            assert_eq!(result.depth as usize, result.pv.len());
+        }
+    }
+
+    #[test]
+    fn stops_when_flag_is_set() {
+        let board = Board::from_fen(START_FEN).unwrap();
+        let table = TranspositionTable::new(1);
+        let stop = Arc::new(AtomicBool::new(true));
+        let mut searcher = Searcher::new(&board, Limits::default(), stop, &table);
+
+        assert!(searcher.search(|_| ()).is_empty());
+    }
+
+    #[test]
+    fn stops_at_node_limit() {
+        let board = Board::from_fen(START_FEN).unwrap();
+        let limits = Limits {
+            nodes: Some(5000),
+            ..Limits::default()
+        };
+        let table = TranspositionTable::new(1);
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut searcher = Searcher::new(&board, limits, stop, &table);
+        let results = searcher.search(|_| ());
+
+        assert!(!results.is_empty());
+        assert!(searcher.nodes < 5000 + CHECK_INTERVAL);
+    }
+
+    #[test]
+    fn null_move_needs_pieces() {
+        let pawns_only = Board::from_fen("4k3/4p3/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+        let knight = Board::from_fen("4k3/4p3/8/8/8/8/4P3/4KN2 w - - 0 1").unwrap();
+        let black_knight = Board::from_fen("4kn2/4p3/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+
+        assert!(!has_non_pawn_material(&pawns_only));
+        assert!(has_non_pawn_material(&knight));
+        assert!(!has_non_pawn_material(&black_knight));
+    }
+
+    #[test]
+    fn reductions_grow_with_depth_and_move_number() {
+        assert_eq!(0, reduction(1, 1));
+        assert!(reduction(3, 4) >= 1);
+        assert!(reduction(10, 30) > reduction(3, 4));
+        assert!(reduction(100, 100) >= reduction(63, 63));
+    }
+
+    #[test]
+    fn pruning_searches_fewer_nodes() {
+        let board = Board::from_fen(
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+        )
+        .unwrap();
+        let limits = Limits {
+            depth: Some(5),
+            ..Limits::default()
+        };
+
+        let mut nodes: Vec<u64> = Vec::new();
+        for pruning in [Pruning::NONE, Pruning::default()] {
+            let table = TranspositionTable::new(1);
+            let stop = Arc::new(AtomicBool::new(false));
+            let mut searcher = Searcher::new(&board, limits.clone(), stop, &table);
+            searcher.set_pruning(pruning);
+            searcher.search(|_| ());
+
+            nodes.push(searcher.nodes);
+        }
+
+        assert!(nodes[1] < nodes[0] / 2, "{:?}", nodes);
+    }
+
+    #[test]
+    fn aspiration_failures_are_reported_as_bounds() {
+        // The score drops from depth 3 to 4 by more than the aspiration window
+        let board = Board::from_fen("2r3k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1").unwrap();
+        let limits = Limits {
+            depth: Some(4),
+            ..Limits::default()
+        };
+        let table = TranspositionTable::new(1);
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut searcher = Searcher::new(&board, limits, stop, &table);
+
+        let mut reports: Vec<(u32, Bound)> = Vec::new();
+        let results = searcher.search(|result| reports.push((result.depth, result.bound)));
+
+        let upper = reports
+            .iter()
+            .position(|&report| report == (4, Bound::Upper));
+        let exact = reports
+            .iter()
+            .position(|&report| report == (4, Bound::Exact));
+        assert!(upper.unwrap() < exact.unwrap());
+
+        assert_eq!(4, results.len());
+        assert!(results.iter().all(|result| result.bound == Bound::Exact));
+    }
+
+    #[test]
+    fn helpers_skip_depths() {
+        let board = Board::from_fen(START_FEN).unwrap();
+        let table = TranspositionTable::new(1);
+        let stop = Arc::new(AtomicBool::new(false));
+        let mut searcher = Searcher::new(&board, Limits::default(), stop, &table);
+        let nodes = Arc::new(AtomicU64::new(0));
+
+        let skipped = |searcher: &Searcher| {
+            return (1..=8)
+                .filter(|&depth| searcher.skips_depth(depth))
+                .collect::<Vec<u32>>();
+        };
+
+        assert!(skipped(&searcher).is_empty());
+
+        searcher.set_thread(1, Arc::clone(&nodes));
+        assert_eq!(vec![1, 3, 5, 7], skipped(&searcher));
+
+        searcher.set_thread(2, Arc::clone(&nodes));
+        assert_eq!(vec![2, 4, 6, 8], skipped(&searcher));
+
+        searcher.set_thread(3, nodes);
+        assert_eq!(vec![2, 3, 6, 7], skipped(&searcher));
+    }
+}
@@ -0,0 +1,292 @@
+use crate::{
+    bitboard::Bitboard,
+    board::Board,
+    move_generation::{attackers_to, is_diagonal, ray_attacks, ALL_DIRECTIONS, ATTACK_RAYS},
+    piece::Pieces,
+    r#move::Move,
+};
+
+/// Piece values for exchanges. The king is worth more than everything else together,
+/// so it only ever captures last.
+pub fn piece_value(piece: Pieces) -> i32 {
+    return match piece {
+        Pieces::Pawns => 100,
+        Pieces::Knights => 320,
+        Pieces::Bishops => 330,
+        Pieces::Rooks => 500,
+        Pieces::Queens => 900,
+        Pieces::Kings => 20_000,
+    };
+}
+
+/// Cheapest first, the order pieces join an exchange in.
+const EXCHANGE_ORDER: [Pieces; 6] = [
+    Pieces::Pawns,
+    Pieces::Knights,
+    Pieces::Bishops,
+    Pieces::Rooks,
+    Pieces::Queens,
+    Pieces::Kings,
+];
+
+/// The slider, if any, that attacks `target` through `removed` now that the piece on
+/// `removed` has left `occupied`.
+fn xray_attackers(board: &Board, target: u32, removed: u32, occupied: Bitboard) -> Bitboard {
+    let Some(direction) = ALL_DIRECTIONS
+        .into_iter()
+        .find(|&direction| ATTACK_RAYS[target as usize][direction] & (1 << removed) > 0)
+    else {
+        return 0;
+    };
+
+    let queens = board.pieces[Pieces::Queens as usize];
+    let sliders = if is_diagonal(direction) {
+        board.pieces[Pieces::Bishops as usize] | queens
+    } else {
+        board.pieces[Pieces::Rooks as usize] | queens
+    };
+
+    return ray_attacks(target, occupied, direction) & sliders & occupied;
+}
+
+fn least_valuable(board: &Board, attackers: Bitboard) -> Option<(Pieces, u32)> {
+    return EXCHANGE_ORDER.into_iter().find_map(|piece| {
+        let pieces = attackers & board.pieces[piece as usize];
+        (pieces > 0).then(|| (piece, pieces.trailing_zeros()))
+    });
+}
+
+/// The state right after `move` is made on the target square.
+struct Exchange {
+    /// What the move wins before any recapture.
+    gain: i32,
+    /// Value of the piece now standing on the target square.
+    on_square: i32,
+    occupied: Bitboard,
+    attackers: Bitboard,
+}
+
+fn start_exchange(board: &Board, r#move: &Move) -> Exchange {
+    let mut occupied = (board.white_pieces | board.black_pieces) ^ (1 << r#move.from);
+
+    let is_en_passant = r#move.piece == Pieces::Pawns
+        && board.game_state.en_passant == Some(r#move.to)
+        && r#move.from % 8 != r#move.to % 8;
+
+    let mut gain = if is_en_passant {
+        let victim = if board.game_state.white_turn {
+            r#move.to + 8
+        } else {
+            r#move.to - 8
+        };
+        occupied ^= 1 << victim;
+        piece_value(Pieces::Pawns)
+    } else {
+        board.piece_at(r#move.to).map_or(0, piece_value)
+    };
+
+    let on_square = match r#move.promotion {
+        Some(promotion) => {
+            gain += piece_value(promotion) - piece_value(Pieces::Pawns);
+            piece_value(promotion)
+        }
+        None => piece_value(r#move.piece),
+    };
+
+    let attackers = (attackers_to(board, r#move.to, occupied)
+        | xray_attackers(board, r#move.to, r#move.from, occupied))
+        & occupied;
+
+    return Exchange {
+        gain,
+        on_square,
+        occupied,
+        attackers,
+    };
+}
+
+fn side_pieces(board: &Board, white: bool) -> Bitboard {
+    return if white {
+        board.white_pieces
+    } else {
+        board.black_pieces
+    };
+}
+
+/// Static exchange evaluation: the material the side to move ends up with when both
+/// sides keep recapturing on the target square of `move` with their cheapest piece,
+/// each free to stop when going on would lose more. Sliders lined up behind other
+/// pieces join in once those have captured.
+pub fn see(board: &Board, r#move: &Move) -> i32 {
+    let Exchange {
+        gain: first_gain,
+        mut on_square,
+        mut occupied,
+        mut attackers,
+    } = start_exchange(board, r#move);
+
+    let mut gains: Vec<i32> = vec![first_gain];
+    let mut white = !board.game_state.white_turn;
+
+    while let Some((piece, square)) = least_valuable(board, attackers & side_pieces(board, white)) {
+        occupied ^= 1 << square;
+        attackers = (attackers | xray_attackers(board, r#move.to, square, occupied)) & occupied;
+
+        // The king may only recapture when nothing can take it back
+        if piece == Pieces::Kings && attackers & side_pieces(board, !white) > 0 {
+            break;
+        }
+
+        gains.push(on_square - gains.last().unwrap());
+        on_square = piece_value(piece);
+        white = !white;
+    }
+
+    // Each side picks the better of capturing and standing pat, from the back
+    while gains.len() > 1 {
+        let last = gains.pop().unwrap();
+        let previous = gains.last_mut().unwrap();
+        *previous = -(-*previous).max(last);
+    }
+
+    return gains[0];
+}
+
+/// Whether `see(board, move) >= threshold`, stopping as soon as the answer is known.
+pub fn see_ge(board: &Board, r#move: &Move, threshold: i32) -> bool {
+    let Exchange {
+        gain,
+        on_square,
+        mut occupied,
+        mut attackers,
+    } = start_exchange(board, r#move);
+
+    // Even if the moved piece is lost for nothing, the capture alone may be enough,
+    // or not even that may reach the threshold
+    let mut balance = gain - threshold;
+    if balance < 0 {
+        return false;
+    }
+
+    balance = on_square - balance;
+    if balance <= 0 {
+        return true;
+    }
+
+    // `balance` is what the side to recapture needs to come out ahead, `result`
+    // whether the side that moved is ahead when the exchange stops here
+    let mut white = !board.game_state.white_turn;
+    let mut result = true;
+
+    while let Some((piece, square)) = least_valuable(board, attackers & side_pieces(board, white)) {
+        occupied ^= 1 << square;
+        attackers = (attackers | xray_attackers(board, r#move.to, square, occupied)) & occupied;
+
+        if piece == Pieces::Kings {
+            return if attackers & side_pieces(board, !white) > 0 {
+                result
+            } else {
+                !result
+            };
+        }
+
+        result = !result;
+        balance = piece_value(piece) - balance;
+        if balance < result as i32 {
+            break;
+        }
+
+        white = !white;
+    }
+
+    return result;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::move_generation::{legal_captures, legal_moves};
+
+    fn see_of(fen: &str, text: &str) -> i32 {
+        let board = Board::from_fen(fen).unwrap();
+        let r#move = legal_moves(&board)
+            .into_iter()
+            .find(|r#move| r#move.to_string() == text)
+            .unwrap();
+
+        return see(&board, &r#move);
+    }
+
+    #[test]
+    fn simple_exchanges() {
+        // Undefended pawn
+        assert_eq!(
+            100,
+            see_of("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1", "e1e5")
+        );
+        // Pawn defended by a pawn, taken with a knight
+        assert_eq!(
+            100 - 320,
+            see_of("4k3/8/3p4/4p3/8/5N2/8/4K3 w - - 0 1", "f3e5")
+        );
+        // Quiet move onto a square an enemy pawn guards
+        assert_eq!(-320, see_of("4k3/8/8/4p3/8/5N2/8/4K3 w - - 0 1", "f3d4"));
+    }
+
+    #[test]
+    fn xray_attackers_join_in() {
+        // Rxe5 Rxe5 Rxe5: the rook on e1 backs up the one on e2
+        assert_eq!(100, see_of("4r1k1/8/8/4p3/8/8/4R3/4R1K1 w - - 0 1", "e2e5"));
+        assert_eq!(
+            100 - 500,
+            see_of("4r1k1/8/8/4p3/8/8/4R3/6K1 w - - 0 1", "e2e5")
+        );
+
+        // Bxe5 Nxe5 Qxe5 with the queen behind the bishop on the diagonal
+        assert_eq!(320, see_of("4k3/3n4/8/4b3/8/8/1B6/Q3K3 w - - 0 1", "b2e5"));
+        assert_eq!(0, see_of("4k3/3n4/8/4b3/8/8/1B6/4K3 w - - 0 1", "b2e5"));
+    }
+
+    #[test]
+    fn king_only_recaptures_safely() {
+        // The rook on d8 covers d2 through d7 once the other rook has captured
+        assert_eq!(900, see_of("3r2k1/3r4/8/8/8/8/3Q4/4K3 b - - 0 1", "d7d2"));
+        assert_eq!(
+            900 - 500,
+            see_of("6k1/3r4/8/8/8/8/3Q4/4K3 b - - 0 1", "d7d2")
+        );
+    }
+
+    #[test]
+    fn promotions_and_en_passant() {
+        assert_eq!(900 - 100, see_of("4k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8q"));
+        assert_eq!(100, see_of("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6"));
+    }
+
+    #[test]
+    fn see_ge_agrees_with_see() {
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10",
+            "4r1k1/8/8/4p3/8/8/4R3/4R1K1 w - - 0 1",
+        ] {
+            let board = Board::from_fen(fen).unwrap();
+
+            for r#move in legal_captures(&board) {
+                let value = see(&board, &r#move);
+
+                for threshold in [-900, -500, -330, -100, 0, 1, 100, 320, 500, 900] {
+                    assert_eq!(
+                        value >= threshold,
+                        see_ge(&board, &r#move, threshold),
+                        "{} {} {}",
+                        fen,
+                        r#move,
+                        threshold
+                    );
+                }
+            }
+        }
+    }
+}
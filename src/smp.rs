@@ -0,0 +1,224 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+use crate::{
+    board::Board,
+    r#move::Move,
+    search::{Limits, Pruning, SearchResult, Searcher},
+    transposition_table::TranspositionTable,
+};
+
+pub const MAX_THREADS: usize = 256;
+
+/// Added to every score before weighing votes, so the worst result still counts.
+const VOTE_OFFSET: i64 = 14;
+
+/// Picks the result to play from the last completed depth of every thread. Each thread
+/// votes for its move, weighted by its depth and by how far its score is above the
+/// worst one. The thread whose move got the most votes wins, the first one on a tie.
+fn vote(results: &[SearchResult]) -> usize {
+    let min_score = results.iter().map(|result| result.score).min().unwrap_or(0);
+    let mut votes: Vec<(Move, i64)> = Vec::new();
+
+    for result in results {
+        let weight = (result.score - min_score) as i64 + VOTE_OFFSET;
+        let weight = weight * result.depth as i64;
+
+        match votes
+            .iter_mut()
+            .find(|(r#move, _)| *r#move == result.best_move)
+        {
+            Some((_, total)) => *total += weight,
+            None => votes.push((result.best_move, weight)),
+        }
+    }
+
+    let votes_for = |r#move: Move| {
+        return votes
+            .iter()
+            .find(|(voted, _)| *voted == r#move)
+            .map_or(0, |(_, total)| *total);
+    };
+
+    let mut best = 0;
+    for (index, result) in results.iter().enumerate() {
+        if votes_for(result.best_move) > votes_for(results[best].best_move) {
+            best = index;
+        }
+    }
+
+    return best;
+}
+
+/// Lazy SMP: `threads` threads search the same position and share only the
+/// transposition table, so what one finds speeds up the others. The main thread keeps
+/// to `limits` and reports through `report`; the helpers skip some depths and stop
+/// when the main thread does. The helpers' results take part in the vote for the
+/// move to play, which is the last result returned.
+pub fn search(
+    board: &Board,
+    limits: Limits,
+    pruning: Pruning,
+    threads: usize,
+    stop: Arc<AtomicBool>,
+    table: &mut TranspositionTable,
+    mut report: impl FnMut(&SearchResult),
+) -> Vec<SearchResult> {
+    table.new_search();
+
+    let table = &*table;
+    let shared_nodes = Arc::new(AtomicU64::new(0));
+    let helpers_stop = Arc::new(AtomicBool::new(false));
+    let helper_limits = Limits {
+        depth: limits.depth,
+        ..Limits::default()
+    };
+
+    return thread::scope(|scope| {
+        let helpers: Vec<_> = (1..threads.clamp(1, MAX_THREADS))
+            .map(|thread| {
+                let mut searcher = Searcher::new(
+                    board,
+                    helper_limits.clone(),
+                    Arc::clone(&helpers_stop),
+                    table,
+                );
+                searcher.set_pruning(pruning);
+                searcher.set_thread(thread, Arc::clone(&shared_nodes));
+
+                scope.spawn(move || searcher.search(|_| ()).pop())
+            })
+            .collect();
+
+        let mut searcher = Searcher::new(board, limits, stop, table);
+        searcher.set_pruning(pruning);
+        searcher.set_thread(0, Arc::clone(&shared_nodes));
+
+        let mut results = searcher.search(&mut report);
+        helpers_stop.store(true, Ordering::Relaxed);
+
+        let mut finals: Vec<SearchResult> = results.last().cloned().into_iter().collect();
+        let main_finished = !finals.is_empty();
+        for helper in helpers {
+            finals.extend(helper.join().expect("Search thread panicked"));
+        }
+
+        if finals.is_empty() {
+            return results;
+        }
+
+        let chosen = vote(&finals);
+        if chosen > 0 || !main_finished {
+            report(&finals[chosen]);
+            results.push(finals.swap_remove(chosen));
+        }
+
+        results
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{board::START_FEN, search::MATE, transposition_table::Bound, uci::parse_move};
+    use std::time::Duration;
+
+    fn result(board: &Board, text: &str, depth: u32, score: i32) -> SearchResult {
+        let best_move = parse_move(board, text).unwrap();
+
+        return SearchResult {
+            depth,
+            best_move,
+            score,
+            bound: Bound::Exact,
+            pv: vec![best_move],
+            nodes: 0,
+            elapsed: Duration::ZERO,
+            hashfull: 0,
+        };
+    }
+
+    #[test]
+    fn votes_weigh_depth_and_score() {
+        let board = Board::from_fen(START_FEN).unwrap();
+
+        // Two helpers agreeing outvote the main thread
+        let agreeing = [
+            result(&board, "e2e4", 10, 30),
+            result(&board, "d2d4", 10, 30),
+            result(&board, "d2d4", 9, 25),
+        ];
+        assert_eq!(1, vote(&agreeing));
+
+        // A much better score from a deeper search beats the majority
+        let deeper = [
+            result(&board, "e2e4", 8, 10),
+            result(&board, "d2d4", 8, 10),
+            result(&board, "g1f3", 12, 200),
+        ];
+        assert_eq!(2, vote(&deeper));
+
+        // Ties go to the main thread
+        let tied = [
+            result(&board, "e2e4", 10, 30),
+            result(&board, "d2d4", 10, 30),
+        ];
+        assert_eq!(0, vote(&tied));
+    }
+
+    #[test]
+    fn threads_find_the_same_mate() {
+        let board = Board::from_fen("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1").unwrap();
+        let limits = Limits {
+            depth: Some(4),
+            ..Limits::default()
+        };
+
+        for threads in [1, 4] {
+            let mut table = TranspositionTable::new(1);
+            let stop = Arc::new(AtomicBool::new(false));
+            let results = search(
+                &board,
+                limits.clone(),
+                Pruning::default(),
+                threads,
+                stop,
+                &mut table,
+                |_| (),
+            );
+            let result = results.last().unwrap();
+
+            assert_eq!("a1a6", result.best_move.to_string());
+            assert_eq!(MATE - 3, result.score);
+        }
+    }
+
+    #[test]
+    fn helpers_stop_with_the_main_thread() {
+        let board = Board::from_fen(START_FEN).unwrap();
+        let limits = Limits {
+            nodes: Some(20_000),
+            ..Limits::default()
+        };
+        let mut table = TranspositionTable::new(1);
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let results = search(
+            &board,
+            limits,
+            Pruning::default(),
+            4,
+            stop,
+            &mut table,
+            |_| (),
+        );
+
+        assert!(!results.is_empty());
+        assert!(table.hashfull() > 0);
+    }
+}
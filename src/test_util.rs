@@ -0,0 +1,8 @@
+/// Xorshift: cheap, and the same sequence on every run, so tests built on random
+/// games or occupancies are reproducible.
+pub fn next_random(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    return *state;
+}
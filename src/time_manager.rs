@@ -0,0 +1,131 @@
+use std::time::{Duration, Instant};
+
+use crate::r#move::Move;
+
+/// Without `movestogo` the remaining time is spread over this many more moves.
+const DEFAULT_MOVES_TO_GO: u64 = 30;
+
+/// The hard limit is this many times the soft one.
+const HARD_FACTOR: u32 = 4;
+
+/// Percentage of the remaining time a single move may take at most.
+const MAX_SHARE: u64 = 75;
+
+/// Percentage of the soft limit to use, by how many iterations in a row the best
+/// move stayed the same.
+const STABILITY_SCALE: [u32; 5] = [150, 125, 100, 85, 75];
+
+/// The score moving this many centipawns between iterations doubles the soft limit.
+const MAX_SWING: u32 = 100;
+
+/// Thinking time for one move under a clock. Iterative deepening stops starting new
+/// iterations after the soft limit, which shrinks while the best move stays the same
+/// and grows when the score swings. The hard limit ends the search no matter what.
+#[derive(Clone, Debug)]
+pub struct TimeManager {
+    start: Instant,
+    soft: Duration,
+    hard: Duration,
+    best_move: Option<Move>,
+    /// Iterations in a row that ended with the same best move.
+    stability: usize,
+    score: Option<i32>,
+    /// How far the score moved in the last iteration.
+    swing: u32,
+}
+
+impl TimeManager {
+    /// All times in milliseconds. `overhead` is kept in reserve for the time it takes
+    /// the move to reach the GUI.
+    pub fn new(time: u64, increment: u64, moves_to_go: Option<u32>, overhead: u64) -> Self {
+        let available = time.saturating_sub(overhead).max(1);
+        let moves_to_go = moves_to_go.map_or(DEFAULT_MOVES_TO_GO, |moves| moves.max(1) as u64);
+        let max_time = (available * MAX_SHARE / 100).max(1);
+
+        let soft = (available / moves_to_go + increment * 3 / 4).clamp(1, max_time);
+        let hard = (soft * HARD_FACTOR as u64).min(max_time);
+
+        TimeManager {
+            start: Instant::now(),
+            soft: Duration::from_millis(soft),
+            hard: Duration::from_millis(hard),
+            best_move: None,
+            stability: 0,
+            score: None,
+            swing: 0,
+        }
+    }
+
+    /// When the search has to stop, even in the middle of an iteration.
+    pub fn deadline(&self) -> Instant {
+        return self.start + self.hard;
+    }
+
+    /// The soft limit adjusted for the iterations so far, never past the hard limit.
+    fn soft_limit(&self) -> Duration {
+        let stability = STABILITY_SCALE[self.stability.min(STABILITY_SCALE.len() - 1)];
+        let swing = 100 + self.swing.min(MAX_SWING) * 100 / MAX_SWING;
+
+        return (self.soft * stability / 100 * swing / 100).min(self.hard);
+    }
+
+    /// Takes in the outcome of a completed iteration. Returns whether the search
+    /// should stop instead of starting the next one.
+    pub fn iteration_done(&mut self, best_move: Move, score: i32) -> bool {
+        if self.best_move == Some(best_move) {
+            self.stability += 1;
+        } else {
+            self.stability = 0;
+        }
+        self.best_move = Some(best_move);
+
+        self.swing = self.score.map_or(0, |previous| previous.abs_diff(score));
+        self.score = Some(score);
+
+        return self.start.elapsed() >= self.soft_limit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        board::{Board, START_FEN},
+        move_generation::legal_moves,
+    };
+
+    #[test]
+    fn limits_from_the_clock() {
+        let clock = TimeManager::new(60_000, 1000, None, 50);
+        assert_eq!(Duration::from_millis(59_950 / 30 + 750), clock.soft);
+        assert_eq!(clock.soft * 4, clock.hard);
+
+        // The last move before the time control may use most, but not all of the time
+        let last_move = TimeManager::new(5000, 0, Some(1), 50);
+        assert_eq!(Duration::from_millis(4950 * 3 / 4), last_move.soft);
+        assert_eq!(last_move.soft, last_move.hard);
+
+        // Almost out of time the overhead still comes off
+        let flagging = TimeManager::new(40, 0, None, 50);
+        assert_eq!(Duration::from_millis(1), flagging.hard);
+    }
+
+    #[test]
+    fn stable_moves_save_time_and_swings_cost_it() {
+        let board = Board::from_fen(START_FEN).unwrap();
+        let moves = legal_moves(&board);
+        let mut clock = TimeManager::new(60_000, 0, None, 0);
+
+        for _ in 0..5 {
+            clock.iteration_done(moves[0], 20);
+        }
+        assert!(clock.soft_limit() < clock.soft);
+
+        clock.iteration_done(moves[1], -80);
+        assert_eq!(clock.soft * 3, clock.soft_limit());
+
+        // Never more than the hard limit
+        clock.iteration_done(moves[2], 500);
+        assert!(clock.soft_limit() <= clock.hard);
+    }
+}
@@ -0,0 +1,390 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{board::PIECES, r#move::Move, search::is_mate_score};
+
+pub const DEFAULT_SIZE_MB: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Bound {
+    Exact,
+    /// The score failed high: the real score is at least this.
+    Lower,
+    /// The score failed low: the real score is at most this.
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Entry {
+    pub best_move: Option<Move>,
+    score: i16,
+    pub depth: u8,
+    pub bound: Bound,
+    age: u8,
+}
+
+impl Entry {
+    const EMPTY: Entry = Entry {
+        best_move: None,
+        score: 0,
+        depth: 0,
+        bound: Bound::Upper,
+        age: 0,
+    };
+
+    fn is_empty(&self) -> bool {
+        return self.depth == 0 && self.best_move.is_none();
+    }
+
+    /// The stored score, with mates measured from the root again.
+    pub fn score(&self, ply: u32) -> i32 {
+        return score_from_table(self.score as i32, ply);
+    }
+
+    /// Packs the entry into 64 bits: the move in the lowest 19, then score, depth,
+    /// bound and age. An empty entry packs to 0.
+    fn pack(&self) -> u64 {
+        let r#move = self.best_move.map_or(0, |r#move| {
+            let piece = PIECES
+                .iter()
+                .position(|&piece| piece == r#move.piece)
+                .unwrap() as u64;
+            let promotion = r#move.promotion.map_or(0, |promotion| {
+                PIECES.iter().position(|&piece| piece == promotion).unwrap() as u64 + 1
+            });
+
+            1 << 18 | promotion << 15 | piece << 12 | (r#move.to as u64) << 6 | r#move.from as u64
+        });
+        let bound = match self.bound {
+            Bound::Upper => 0,
+            Bound::Lower => 1,
+            Bound::Exact => 2,
+        };
+
+        return r#move
+            | (self.score as u16 as u64) << 19
+            | (self.depth as u64) << 35
+            | bound << 43
+            | (self.age as u64) << 45;
+    }
+
+    fn unpack(data: u64) -> Entry {
+        let best_move = (data >> 18 & 1 == 1).then(|| Move {
+            from: (data & 0x3F) as u32,
+            to: (data >> 6 & 0x3F) as u32,
+            piece: PIECES[(data >> 12 & 0x7) as usize],
+            promotion: match data >> 15 & 0x7 {
+                0 => None,
+                promotion => Some(PIECES[promotion as usize - 1]),
+            },
+        });
+        let bound = match data >> 43 & 0x3 {
+            0 => Bound::Upper,
+            1 => Bound::Lower,
+            _ => Bound::Exact,
+        };
+
+        return Entry {
+            best_move,
+            score: (data >> 19) as u16 as i16,
+            depth: (data >> 35) as u8,
+            bound,
+            age: (data >> 45) as u8,
+        };
+    }
+}
+
+/// One table entry, written and read by several search threads without locking. The
+/// key is stored XORed with the data, so a slot whose two words come from different
+/// writes no longer matches any position and reads as a miss.
+#[derive(Default)]
+struct Slot {
+    key: AtomicU64,
+    data: AtomicU64,
+}
+
+impl Slot {
+    fn load(&self, hash: u64) -> Option<Entry> {
+        let key = self.key.load(Ordering::Relaxed);
+        let data = self.data.load(Ordering::Relaxed);
+
+        if key ^ data != hash {
+            return None;
+        }
+
+        let entry = Entry::unpack(data);
+        return if entry.is_empty() { None } else { Some(entry) };
+    }
+
+    /// The entry whatever position it belongs to, for replacement decisions.
+    fn load_any(&self) -> Entry {
+        return Entry::unpack(self.data.load(Ordering::Relaxed));
+    }
+
+    fn store(&self, hash: u64, entry: &Entry) {
+        let data = entry.pack();
+        self.key.store(hash ^ data, Ordering::Relaxed);
+        self.data.store(data, Ordering::Relaxed);
+    }
+}
+
+/// Mate scores are stored as the distance from the node instead of from the root,
+/// so they stay right when the position is reached through a different path.
+fn score_to_table(score: i32, ply: u32) -> i32 {
+    if !is_mate_score(score) {
+        return score;
+    }
+
+    return if score > 0 {
+        score + ply as i32
+    } else {
+        score - ply as i32
+    };
+}
+
+fn score_from_table(score: i32, ply: u32) -> i32 {
+    if !is_mate_score(score) {
+        return score;
+    }
+
+    return if score > 0 {
+        score - ply as i32
+    } else {
+        score + ply as i32
+    };
+}
+
+/// Shared by all search threads, which probe and store through `&self`.
+pub struct TranspositionTable {
+    entries: Vec<Slot>,
+    /// Bumped for every search so entries from earlier searches get replaced first.
+    age: u8,
+}
+
+impl TranspositionTable {
+    /// A table of at most `size_mb` megabytes, rounded down to a power of two entries.
+    pub fn new(size_mb: usize) -> Self {
+        let bytes = size_mb.max(1) * 1024 * 1024;
+        let capacity = bytes / std::mem::size_of::<Slot>();
+        let length = 1 << capacity.ilog2();
+
+        TranspositionTable {
+            entries: (0..length).map(|_| Slot::default()).collect(),
+            age: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        return self.entries.len();
+    }
+
+    pub fn clear(&mut self) {
+        for slot in &self.entries {
+            slot.store(0, &Entry::EMPTY);
+        }
+        self.age = 0;
+    }
+
+    pub fn new_search(&mut self) {
+        self.age = self.age.wrapping_add(1);
+    }
+
+    fn index(&self, hash: u64) -> usize {
+        return hash as usize & (self.entries.len() - 1);
+    }
+
+    pub fn probe(&self, hash: u64) -> Option<Entry> {
+        return self.entries[self.index(hash)].load(hash);
+    }
+
+    /// Stores a search result. An entry of another position is only replaced when it
+    /// is from an earlier search or wasn't searched deeper.
+    pub fn store(
+        &self,
+        hash: u64,
+        depth: u32,
+        ply: u32,
+        score: i32,
+        bound: Bound,
+        best_move: Option<Move>,
+    ) {
+        let slot = &self.entries[self.index(hash)];
+        let same_position = slot.load(hash);
+        let old = same_position.unwrap_or_else(|| slot.load_any());
+
+        if !old.is_empty()
+            && same_position.is_none()
+            && old.age == self.age
+            && old.depth as u32 > depth
+        {
+            return;
+        }
+
+        let entry = Entry {
+            // Keep the old move when this search didn't find one, e.g. after failing low
+            best_move: best_move.or(same_position.and_then(|entry| entry.best_move)),
+            score: score_to_table(score, ply) as i16,
+            depth: depth.min(u8::MAX as u32) as u8,
+            bound,
+            age: self.age,
+        };
+        slot.store(hash, &entry);
+    }
+
+    /// How full the table is in per-mille, counting entries of the current search.
+    pub fn hashfull(&self) -> u32 {
+        let sample = self.entries.len().min(1000);
+        let used = self.entries[..sample]
+            .iter()
+            .map(Slot::load_any)
+            .filter(|entry| !entry.is_empty() && entry.age == self.age)
+            .count();
+
+        return (used * 1000 / sample) as u32;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{piece::Pieces, search::MATE};
+
+    const MOVE: Move = Move {
+        from: 52,
+        to: 36,
+        piece: Pieces::Pawns,
+        promotion: None,
+    };
+
+    #[test]
+    fn size_is_power_of_two() {
+        let table = TranspositionTable::new(1);
+
+        assert!(table.len().is_power_of_two());
+        assert!(table.len() * std::mem::size_of::<Slot>() <= 1024 * 1024);
+        assert!(table.len() * 2 * std::mem::size_of::<Slot>() > 1024 * 1024);
+    }
+
+    #[test]
+    fn store_and_probe() {
+        let mut table = TranspositionTable::new(1);
+        let hash = 0x1234_5678_9ABC_DEF0;
+
+        assert!(table.probe(hash).is_none());
+
+        table.store(hash, 5, 0, 42, Bound::Exact, Some(MOVE));
+        let entry = table.probe(hash).unwrap();
+        assert_eq!(42, entry.score(0));
+        assert_eq!(5, entry.depth);
+        assert_eq!(Bound::Exact, entry.bound);
+        assert_eq!(Some(MOVE), entry.best_move);
+
+        // Same slot, different verification bits
+        assert!(table.probe(hash ^ (1 << 40)).is_none());
+
+        table.clear();
+        assert!(table.probe(hash).is_none());
+    }
+
+    #[test]
+    fn entries_pack_into_one_word() {
+        let promotion = Move {
+            from: 9,
+            to: 0,
+            piece: Pieces::Pawns,
+            promotion: Some(Pieces::Knights),
+        };
+
+        for (best_move, score, bound) in [
+            (Some(MOVE), -MATE + 3, Bound::Upper),
+            (Some(promotion), 1234, Bound::Lower),
+            (None, 0, Bound::Exact),
+        ] {
+            let entry = Entry {
+                best_move,
+                score: score as i16,
+                depth: 200,
+                bound,
+                age: 255,
+            };
+            let unpacked = Entry::unpack(entry.pack());
+
+            assert_eq!(best_move, unpacked.best_move);
+            assert_eq!(score, unpacked.score(0));
+            assert_eq!(200, unpacked.depth);
+            assert_eq!(bound, unpacked.bound);
+            assert_eq!(255, unpacked.age);
+        }
+        assert_eq!(0, Entry::EMPTY.pack());
+    }
+
+    #[test]
+    fn torn_writes_read_as_misses() {
+        let table = TranspositionTable::new(1);
+        let hash = 0x1234_5678_9ABC_DEF0;
+        let other = hash ^ 1 << 40;
+
+        table.store(hash, 5, 0, 42, Bound::Exact, Some(MOVE));
+        let slot = &table.entries[table.index(hash)];
+        let key = slot.key.load(Ordering::Relaxed);
+
+        // Another thread got as far as the data of a different entry
+        table.store(other, 9, 0, -7, Bound::Lower, None);
+        slot.key.store(key, Ordering::Relaxed);
+
+        assert!(table.probe(hash).is_none());
+        assert!(table.probe(other).is_none());
+    }
+
+    #[test]
+    fn mate_scores_are_relative_to_the_node() {
+        let table = TranspositionTable::new(1);
+
+        // Mate in 3 plies found 4 plies from the root is a mate in 3 from the node
+        table.store(1, 3, 4, MATE - 7, Bound::Exact, None);
+        let entry = table.probe(1).unwrap();
+        assert_eq!(MATE - 3, entry.score(0));
+        assert_eq!(MATE - 5, entry.score(2));
+
+        table.store(1, 3, 4, -MATE + 7, Bound::Exact, None);
+        assert_eq!(-MATE + 5, table.probe(1).unwrap().score(2));
+    }
+
+    #[test]
+    fn replacement_prefers_depth_and_age() {
+        let mut table = TranspositionTable::new(1);
+        let deep = 7;
+        let shallow = deep | 1 << 40;
+
+        table.store(deep, 8, 0, 10, Bound::Exact, Some(MOVE));
+        table.store(shallow, 2, 0, 20, Bound::Exact, None);
+        assert!(table.probe(shallow).is_none());
+        assert_eq!(10, table.probe(deep).unwrap().score(0));
+
+        // The same position is always updated
+        table.store(deep, 3, 0, 30, Bound::Lower, None);
+        let entry = table.probe(deep).unwrap();
+        assert_eq!(30, entry.score(0));
+        assert_eq!(Some(MOVE), entry.best_move);
+
+        // Anything from an earlier search may be replaced
+        table.store(deep, 8, 0, 10, Bound::Exact, Some(MOVE));
+        table.new_search();
+        table.store(shallow, 2, 0, 20, Bound::Exact, None);
+        assert!(table.probe(deep).is_none());
+        assert_eq!(20, table.probe(shallow).unwrap().score(0));
+    }
+
+    #[test]
+    fn hashfull_counts_current_search() {
+        let mut table = TranspositionTable::new(1);
+        assert_eq!(0, table.hashfull());
+
+        for hash in 0..500 {
+            table.store(hash, 1, 0, 0, Bound::Exact, None);
+        }
+        assert_eq!(500, table.hashfull());
+
+        table.new_search();
+        assert_eq!(0, table.hashfull());
+    }
+}
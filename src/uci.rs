@@ -0,0 +1,678 @@
+use std::{
+    io::{self, BufRead, Write},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    board::{Board, START_FEN},
+    move_generation::legal_moves,
+    r#move::Move,
+    search::{is_mate_score, Limits, Pruning, SearchResult, MATE},
+    smp::{self, MAX_THREADS},
+    time_manager::TimeManager,
+    transposition_table::{Bound, TranspositionTable, DEFAULT_SIZE_MB},
+};
+
+const ENGINE_NAME: &str = "chess_prototype";
+const ENGINE_AUTHOR: &str = "IkBenJur";
+const MAX_HASH_MB: usize = 4096;
+
+/// Milliseconds kept in reserve on every move for the GUI and the operating system.
+const DEFAULT_MOVE_OVERHEAD: u64 = 50;
+const MAX_MOVE_OVERHEAD: u64 = 5000;
+
+/// UCI names of the `check` options that switch parts of the search on and off.
+const PRUNING_OPTIONS: [&str; 4] = [
+    "NullMove",
+    "LateMoveReductions",
+    "ReverseFutility",
+    "Futility",
+];
+
+fn pruning_switch<'a>(pruning: &'a mut Pruning, name: &str) -> Option<&'a mut bool> {
+    return match name {
+        "NullMove" => Some(&mut pruning.null_move),
+        "LateMoveReductions" => Some(&mut pruning.late_move_reductions),
+        "ReverseFutility" => Some(&mut pruning.reverse_futility),
+        "Futility" => Some(&mut pruning.futility),
+        _ => None,
+    };
+}
+
+/// Limits sent with `go`. Times are in milliseconds.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GoParams {
+    pub depth: Option<u32>,
+    pub move_time: Option<u64>,
+    pub white_time: Option<u64>,
+    pub black_time: Option<u64>,
+    pub white_increment: Option<u64>,
+    pub black_increment: Option<u64>,
+    pub moves_to_go: Option<u32>,
+    pub nodes: Option<u64>,
+    pub infinite: bool,
+}
+
+/// Where the engine writes its replies. Shared with the search thread, which sends
+/// `info` and `bestmove` while the main thread keeps reading commands.
+pub type Output = Arc<Mutex<dyn Write + Send>>;
+
+pub struct Uci {
+    board: Board,
+    /// Kept between searches; locked by the search thread while it runs.
+    table: Arc<Mutex<TranspositionTable>>,
+    stop: Arc<AtomicBool>,
+    search: Option<JoinHandle<()>>,
+    pruning: Pruning,
+    move_overhead: u64,
+    threads: usize,
+    out: Output,
+}
+
+/// Finds the legal move written in long algebraic notation, e.g. `e2e4` or `e7e8q`.
+pub fn parse_move(board: &Board, text: &str) -> Option<Move> {
+    return legal_moves(board)
+        .into_iter()
+        .find(|r#move| r#move.to_string() == text);
+}
+
+/// Parses the arguments of `position`: `startpos` or `fen <fen>`, optionally followed
+/// by `moves` and the moves played from there.
+pub fn parse_position(args: &[&str]) -> Result<Board, String> {
+    let moves_index = args.iter().position(|&arg| arg == "moves");
+    let (setup, moves) = match moves_index {
+        Some(index) => (&args[..index], &args[index + 1..]),
+        None => (args, &args[args.len()..]),
+    };
+
+    let mut board = match setup.first() {
+        Some(&"startpos") => Board::from_fen(START_FEN).unwrap(),
+        Some(&"fen") => {
+            let board =
+                Board::from_fen(&setup[1..].join(" ")).map_err(|error| error.to_string())?;
+            board.validate().map_err(|error| error.to_string())?;
+            board
+        }
+        _ => return Err("expected startpos or fen".to_string()),
+    };
+
+    for &text in moves {
+        let r#move = parse_move(&board, text).ok_or_else(|| format!("illegal move {}", text))?;
+        board.make_move(&r#move);
+    }
+
+    return Ok(board);
+}
+
+fn parse_value<T: std::str::FromStr>(value: Option<&&str>) -> Option<T> {
+    return value.and_then(|value| value.parse().ok());
+}
+
+pub fn parse_go(args: &[&str]) -> GoParams {
+    let mut params = GoParams::default();
+    let mut args = args.iter();
+
+    while let Some(&arg) = args.next() {
+        match arg {
+            "depth" => params.depth = parse_value(args.next()),
+            "movetime" => params.move_time = parse_value(args.next()),
+            "wtime" => params.white_time = parse_value(args.next()),
+            "btime" => params.black_time = parse_value(args.next()),
+            "winc" => params.white_increment = parse_value(args.next()),
+            "binc" => params.black_increment = parse_value(args.next()),
+            "movestogo" => params.moves_to_go = parse_value(args.next()),
+            "nodes" => params.nodes = parse_value(args.next()),
+            "infinite" => params.infinite = true,
+            _ => (),
+        }
+    }
+
+    return params;
+}
+
+/// Turns the `go` parameters into search limits. A fixed `movetime` is used up to the
+/// move overhead, a clock is left to the time manager.
+fn limits(board: &Board, params: &GoParams, move_overhead: u64) -> Limits {
+    if params.infinite {
+        return Limits::default();
+    }
+
+    let (time, increment) = if board.game_state.white_turn {
+        (params.white_time, params.white_increment)
+    } else {
+        (params.black_time, params.black_increment)
+    };
+
+    let time_manager = match (params.move_time, time) {
+        (None, Some(time)) => Some(TimeManager::new(
+            time,
+            increment.unwrap_or(0),
+            params.moves_to_go,
+            move_overhead,
+        )),
+        _ => None,
+    };
+
+    let deadline = match (&time_manager, params.move_time) {
+        (Some(time_manager), _) => Some(time_manager.deadline()),
+        (None, Some(time)) => {
+            let time = time.saturating_sub(move_overhead).max(1);
+            Some(Instant::now() + Duration::from_millis(time))
+        }
+        (None, None) => None,
+    };
+
+    return Limits {
+        depth: params.depth,
+        nodes: params.nodes,
+        deadline,
+        time_manager,
+    };
+}
+
+/// Formats a score as `cp <centipawns>` or `mate <moves>`, negative when being mated.
+fn format_score(score: i32) -> String {
+    if is_mate_score(score) {
+        let moves = (MATE - score.abs() + 1) / 2;
+        return format!("mate {}", if score > 0 { moves } else { -moves });
+    }
+
+    return format!("cp {}", score);
+}
+
+fn format_info(result: &SearchResult) -> String {
+    let millis = result.elapsed.as_millis() as u64;
+    let nps = result.nodes * 1000 / millis.max(1);
+    let pv: Vec<String> = result.pv.iter().map(Move::to_string).collect();
+    let bound = match result.bound {
+        Bound::Exact => "",
+        Bound::Lower => " lowerbound",
+        Bound::Upper => " upperbound",
+    };
+
+    return format!(
+        "info depth {} score {}{} nodes {} nps {} time {} hashfull {} pv {}",
+        result.depth,
+        format_score(result.score),
+        bound,
+        result.nodes,
+        nps,
+        millis,
+        result.hashfull,
+        pv.join(" ")
+    );
+}
+
+/// Searches the position and picks the move to play. Falls back to the first legal
+/// move when not even the first depth completed.
+fn think(
+    board: &Board,
+    limits: Limits,
+    pruning: Pruning,
+    threads: usize,
+    stop: Arc<AtomicBool>,
+    table: &mut TranspositionTable,
+    out: &Output,
+) -> Option<Move> {
+    let results = smp::search(board, limits, pruning, threads, stop, table, |result| {
+        let mut out = out.lock().unwrap();
+        // Nothing to do about a GUI that stopped listening, the search finishes anyway
+        let _ = writeln!(out, "{}", format_info(result)).and_then(|_| out.flush());
+    });
+
+    return match results.last() {
+        Some(result) => Some(result.best_move),
+        None => legal_moves(board).first().copied(),
+    };
+}
+
+impl Uci {
+    pub fn new(out: Output) -> Self {
+        Uci {
+            board: Board::from_fen(START_FEN).unwrap(),
+            table: Arc::new(Mutex::new(TranspositionTable::new(DEFAULT_SIZE_MB))),
+            stop: Arc::new(AtomicBool::new(false)),
+            search: None,
+            pruning: Pruning::default(),
+            move_overhead: DEFAULT_MOVE_OVERHEAD,
+            threads: 1,
+            out,
+        }
+    }
+
+    fn stop_search(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+
+        if let Some(search) = self.search.take() {
+            search.join().expect("Search thread panicked");
+        }
+    }
+
+    fn go(&mut self, params: GoParams) {
+        self.stop_search();
+        self.stop.store(false, Ordering::SeqCst);
+
+        // The clock started running when `go` was sent, not when the search thread
+        // gets going
+        let search_limits = limits(&self.board, &params, self.move_overhead);
+        let board = self.board.clone();
+        let stop = Arc::clone(&self.stop);
+        let table = Arc::clone(&self.table);
+        let pruning = self.pruning;
+        let threads = self.threads;
+        let out = Arc::clone(&self.out);
+
+        self.search = Some(thread::spawn(move || {
+            let mut table = table.lock().unwrap();
+            let best_move = think(
+                &board,
+                search_limits,
+                pruning,
+                threads,
+                Arc::clone(&stop),
+                &mut table,
+                &out,
+            );
+
+            // `bestmove` may only be sent after `stop` when searching infinitely
+            while params.infinite && !stop.load(Ordering::SeqCst) {
+                thread::sleep(Duration::from_millis(1));
+            }
+
+            let mut out = out.lock().unwrap();
+            let _ = match best_move {
+                Some(best_move) => writeln!(out, "bestmove {}", best_move),
+                None => writeln!(out, "bestmove 0000"),
+            }
+            .and_then(|_| out.flush());
+        }));
+    }
+
+    /// `setoption name <name> value <value>`. Unknown options are ignored.
+    fn set_option(&mut self, args: &[&str]) {
+        let value_index = args.iter().position(|&arg| arg == "value");
+        let (name, value) = match value_index {
+            Some(index) => (args[1..index].join(" "), args.get(index + 1)),
+            None => (args[1..].join(" "), None),
+        };
+
+        if name.eq_ignore_ascii_case("Hash") {
+            if let Some(size_mb) = parse_value::<usize>(value) {
+                self.stop_search();
+                *self.table.lock().unwrap() =
+                    TranspositionTable::new(size_mb.clamp(1, MAX_HASH_MB));
+            }
+        } else if name.eq_ignore_ascii_case("Threads") {
+            if let Some(threads) = parse_value::<usize>(value) {
+                self.threads = threads.clamp(1, MAX_THREADS);
+            }
+        } else if name.eq_ignore_ascii_case("Move Overhead") {
+            if let Some(overhead) = parse_value::<u64>(value) {
+                self.move_overhead = overhead.min(MAX_MOVE_OVERHEAD);
+            }
+        } else if let Some(option) = PRUNING_OPTIONS
+            .into_iter()
+            .find(|option| name.eq_ignore_ascii_case(option))
+        {
+            if let Some(enabled) = parse_value::<bool>(value) {
+                *pruning_switch(&mut self.pruning, option).unwrap() = enabled;
+            }
+        }
+    }
+
+    /// Handles one line of input. Returns false once the engine should quit.
+    pub fn handle_command(&mut self, line: &str) -> io::Result<bool> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        // Written in one go at the end, so the output isn't locked while waiting for
+        // the search thread to stop
+        let mut out: Vec<u8> = Vec::new();
+
+        match words.first() {
+            Some(&"uci") => {
+                writeln!(out, "id name {}", ENGINE_NAME)?;
+                writeln!(out, "id author {}", ENGINE_AUTHOR)?;
+                writeln!(
+                    out,
+                    "option name Hash type spin default {} min 1 max {}",
+                    DEFAULT_SIZE_MB, MAX_HASH_MB
+                )?;
+                writeln!(
+                    out,
+                    "option name Threads type spin default 1 min 1 max {}",
+                    MAX_THREADS
+                )?;
+                writeln!(
+                    out,
+                    "option name Move Overhead type spin default {} min 0 max {}",
+                    DEFAULT_MOVE_OVERHEAD, MAX_MOVE_OVERHEAD
+                )?;
+                for option in PRUNING_OPTIONS {
+                    let enabled = *pruning_switch(&mut Pruning::default(), option).unwrap();
+                    writeln!(out, "option name {} type check default {}", option, enabled)?;
+                }
+                writeln!(out, "uciok")?;
+            }
+            Some(&"isready") => writeln!(out, "readyok")?,
+            Some(&"ucinewgame") => {
+                self.stop_search();
+                self.board = Board::from_fen(START_FEN).unwrap();
+                self.table.lock().unwrap().clear();
+            }
+            Some(&"setoption") if words.get(1) == Some(&"name") => self.set_option(&words[1..]),
+            Some(&"position") => {
+                self.stop_search();
+                match parse_position(&words[1..]) {
+                    Ok(board) => self.board = board,
+                    Err(error) => writeln!(out, "info string invalid position: {}", error)?,
+                }
+            }
+            Some(&"go") => self.go(parse_go(&words[1..])),
+            Some(&"stop") => self.stop_search(),
+            Some(&"quit") => {
+                self.stop_search();
+                return Ok(false);
+            }
+            Some(&"d") => {
+                write!(out, "{}", self.board)?;
+                writeln!(out, "Fen: {}", self.board.to_fen())?;
+            }
+            _ => (),
+        }
+
+        let mut shared = self.out.lock().unwrap();
+        shared.write_all(&out)?;
+        shared.flush()?;
+        return Ok(true);
+    }
+}
+
+/// Reads UCI commands from stdin until `quit` or the end of input.
+pub fn run() {
+    let mut uci = Uci::new(Arc::new(Mutex::new(io::stdout())));
+    let stdin = io::stdin();
+
+    for line in stdin.lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+
+        match uci.handle_command(&line) {
+            Ok(true) => (),
+            _ => return,
+        }
+    }
+
+    uci.stop_search();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An engine writing into a buffer the test can read.
+    fn engine() -> (Uci, Arc<Mutex<Vec<u8>>>) {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let uci = Uci::new(Arc::clone(&output) as Output);
+
+        return (uci, output);
+    }
+
+    fn run_commands(uci: &mut Uci, commands: &[&str]) {
+        for command in commands {
+            uci.handle_command(command).unwrap();
+        }
+    }
+
+    fn output(buffer: &Mutex<Vec<u8>>) -> String {
+        return String::from_utf8(std::mem::take(&mut *buffer.lock().unwrap())).unwrap();
+    }
+
+    #[test]
+    fn handshake() {
+        let (mut uci, buffer) = engine();
+        run_commands(&mut uci, &["uci", "isready"]);
+        let output = output(&buffer);
+
+        assert_eq!(
+            "id name chess_prototype\nid author IkBenJur\n\
+             option name Hash type spin default 16 min 1 max 4096\n\
+             option name Threads type spin default 1 min 1 max 256\n\
+             option name Move Overhead type spin default 50 min 0 max 5000\n\
+             option name NullMove type check default true\n\
+             option name LateMoveReductions type check default true\n\
+             option name ReverseFutility type check default true\n\
+             option name Futility type check default true\n\
+             uciok\nreadyok\n",
+            output
+        );
+    }
+
+    #[test]
+    fn position_with_moves() {
+        let board = parse_position(&["startpos", "moves", "e2e4", "c7c5", "g1f3"]).unwrap();
+        assert_eq!(
+            "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2",
+            board.to_fen()
+        );
+
+        let args: Vec<&str> = "fen 4k3/P7/8/8/8/8/8/4K3 w - - 0 1 moves a7a8q e8d7"
+            .split(' ')
+            .collect();
+        let board = parse_position(&args).unwrap();
+        assert_eq!("Q7/3k4/8/8/8/8/8/4K3 w - - 1 2", board.to_fen());
+    }
+
+    #[test]
+    fn invalid_positions_keep_the_old_one() {
+        let (mut uci, buffer) = engine();
+        run_commands(
+            &mut uci,
+            &[
+                "position startpos moves e2e4",
+                "position startpos moves e2e5",
+                "position fen 8/8/8/8/8/8/8/8 w - - 0 1",
+            ],
+        );
+
+        let output = output(&buffer);
+        assert_eq!(2, output.matches("info string invalid position").count());
+        assert_eq!(
+            "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1",
+            uci.board.to_fen()
+        );
+    }
+
+    #[test]
+    fn display_shows_the_fen() {
+        let (mut uci, buffer) = engine();
+        run_commands(&mut uci, &["position startpos moves e2e4", "d"]);
+
+        assert!(output(&buffer)
+            .ends_with("Fen: rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1\n"));
+    }
+
+    #[test]
+    fn go_parameters() {
+        let args: Vec<&str> =
+            "wtime 300000 btime 290000 winc 2000 binc 2000 movestogo 40 depth 12 nodes 1000000"
+                .split(' ')
+                .collect();
+
+        assert_eq!(
+            GoParams {
+                depth: Some(12),
+                move_time: None,
+                white_time: Some(300000),
+                black_time: Some(290000),
+                white_increment: Some(2000),
+                black_increment: Some(2000),
+                moves_to_go: Some(40),
+                nodes: Some(1000000),
+                infinite: false,
+            },
+            parse_go(&args)
+        );
+
+        assert_eq!(
+            GoParams {
+                move_time: Some(500),
+                ..GoParams::default()
+            },
+            parse_go(&["movetime", "500"])
+        );
+        assert!(parse_go(&["infinite"]).infinite);
+    }
+
+    #[test]
+    fn score_formatting() {
+        assert_eq!("cp 35", format_score(35));
+        assert_eq!("cp -120", format_score(-120));
+        assert_eq!("mate 1", format_score(MATE - 1));
+        assert_eq!("mate 2", format_score(MATE - 3));
+        assert_eq!("mate -1", format_score(-MATE + 2));
+    }
+
+    #[test]
+    fn bounds_in_info_lines() {
+        let board = Board::from_fen(START_FEN).unwrap();
+        let r#move = parse_move(&board, "e2e4").unwrap();
+        let mut result = SearchResult {
+            depth: 5,
+            best_move: r#move,
+            score: 40,
+            bound: Bound::Lower,
+            pv: vec![r#move],
+            nodes: 2000,
+            elapsed: Duration::from_millis(10),
+            hashfull: 3,
+        };
+
+        assert_eq!(
+            "info depth 5 score cp 40 lowerbound nodes 2000 nps 200000 time 10 hashfull 3 pv e2e4",
+            format_info(&result)
+        );
+
+        result.bound = Bound::Upper;
+        assert!(format_info(&result).contains("score cp 40 upperbound nodes"));
+    }
+
+    #[test]
+    fn clock_limits() {
+        let board = Board::from_fen(START_FEN).unwrap();
+
+        let params = parse_go(&["depth", "5"]);
+        let depth_only = limits(&board, &params, 50);
+        assert_eq!(Some(5), depth_only.depth);
+        assert!(depth_only.deadline.is_none());
+        assert!(depth_only.time_manager.is_none());
+
+        // The hard limit of the time manager is the deadline
+        let params = parse_go(&["wtime", "60000", "btime", "1000", "winc", "1000"]);
+        let clock = limits(&board, &params, 50);
+        let budget = clock.deadline.unwrap().duration_since(Instant::now());
+        assert_eq!(
+            clock.time_manager.unwrap().deadline(),
+            clock.deadline.unwrap()
+        );
+        assert!(budget <= Duration::from_millis(4 * (59_950 / 30 + 750)));
+        assert!(budget >= Duration::from_millis(10_000));
+
+        let move_time = limits(&board, &parse_go(&["movetime", "1000"]), 200);
+        let budget = move_time.deadline.unwrap().duration_since(Instant::now());
+        assert!(budget <= Duration::from_millis(800));
+        assert!(move_time.time_manager.is_none());
+
+        assert!(limits(&board, &parse_go(&["infinite"]), 50)
+            .deadline
+            .is_none());
+    }
+
+    #[test]
+    fn threads_option() {
+        let (mut uci, _) = engine();
+        assert_eq!(1, uci.threads);
+
+        run_commands(&mut uci, &["setoption name Threads value 8"]);
+        assert_eq!(8, uci.threads);
+
+        run_commands(&mut uci, &["setoption name Threads value 0"]);
+        assert_eq!(1, uci.threads);
+    }
+
+    #[test]
+    fn move_overhead_option() {
+        let (mut uci, _) = engine();
+        assert_eq!(DEFAULT_MOVE_OVERHEAD, uci.move_overhead);
+
+        run_commands(&mut uci, &["setoption name Move Overhead value 120"]);
+        assert_eq!(120, uci.move_overhead);
+
+        run_commands(&mut uci, &["setoption name Move Overhead value 100000"]);
+        assert_eq!(MAX_MOVE_OVERHEAD, uci.move_overhead);
+    }
+
+    #[test]
+    fn hash_option_resizes_table() {
+        let (mut uci, _) = engine();
+        let default_len = uci.table.lock().unwrap().len();
+
+        run_commands(&mut uci, &["setoption name Hash value 32"]);
+        assert_eq!(default_len * 2, uci.table.lock().unwrap().len());
+
+        run_commands(&mut uci, &["setoption name Hash value nonsense"]);
+        assert_eq!(default_len * 2, uci.table.lock().unwrap().len());
+    }
+
+    #[test]
+    fn pruning_options_switch_techniques() {
+        let (mut uci, _) = engine();
+
+        run_commands(
+            &mut uci,
+            &[
+                "setoption name NullMove value false",
+                "setoption name futility value false",
+                "setoption name ReverseFutility value maybe",
+            ],
+        );
+        assert_eq!(
+            Pruning {
+                null_move: false,
+                futility: false,
+                ..Pruning::default()
+            },
+            uci.pruning
+        );
+    }
+
+    #[test]
+    fn search_reports_through_the_output() {
+        let (mut uci, buffer) = engine();
+        run_commands(&mut uci, &["position startpos", "go depth 1"]);
+        uci.search.take().unwrap().join().unwrap();
+
+        let output = output(&buffer);
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0].starts_with("info depth 1 "), "{}", output);
+
+        let best_move = lines.last().unwrap().strip_prefix("bestmove ").unwrap();
+        assert!(parse_move(&uci.board, best_move).is_some());
+    }
+
+    #[test]
+    fn infinite_search_waits_for_stop() {
+        let (mut uci, _) = engine();
+        run_commands(&mut uci, &["go infinite"]);
+
+        thread::sleep(Duration::from_millis(20));
+        assert!(!uci.search.as_ref().unwrap().is_finished());
+
+        run_commands(&mut uci, &["stop"]);
+        assert!(uci.search.is_none());
+    }
+}
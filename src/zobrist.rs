@@ -0,0 +1,204 @@
+use crate::{
+    board::{Board, GameState},
+    piece::Pieces,
+};
+
+/// SplitMix64, usable at compile time so the keys are the same in every build.
+const fn split_mix(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E3779B97F4A7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+
+    return (state, z ^ (z >> 31));
+}
+
+const fn generate_keys<const N: usize>(seed: u64) -> [u64; N] {
+    let mut keys = [0; N];
+    let mut state = seed;
+    let mut i = 0;
+
+    while i < N {
+        let (next_state, key) = split_mix(state);
+        keys[i] = key;
+        state = next_state;
+        i += 1;
+    }
+
+    return keys;
+}
+
+/// One key per colour, piece and square, indexed by `piece_key`.
+const PIECE_SQUARE_KEYS: [u64; 2 * 6 * 64] = generate_keys(0x5A0B_2F1E_11C4_7D39);
+const CASTLING_KEYS: [u64; 4] = generate_keys(0x3C6E_F372_FE94_F82B);
+const EN_PASSANT_FILE_KEYS: [u64; 8] = generate_keys(0xA54F_F53A_5F1D_36F1);
+const BLACK_TO_MOVE_KEY: u64 = generate_keys::<1>(0x510E_527F_ADE6_82D1)[0];
+
+pub fn piece_key(piece: Pieces, white: bool, square: u32) -> u64 {
+    let color_index = if white { 0 } else { 1 };
+
+    return PIECE_SQUARE_KEYS[(color_index * 6 + piece as usize) * 64 + square as usize];
+}
+
+/// Everything in the key besides the pieces: side to move, castling rights and the
+/// file of the en passant square.
+pub fn state_key(game_state: &GameState) -> u64 {
+    let mut key = 0;
+
+    let castling = &game_state.castling;
+    for (index, allowed) in [
+        castling.white_king_side,
+        castling.white_queen_side,
+        castling.black_king_side,
+        castling.black_queen_side,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        if allowed {
+            key ^= CASTLING_KEYS[index];
+        }
+    }
+
+    if let Some(en_passant) = game_state.en_passant {
+        key ^= EN_PASSANT_FILE_KEYS[(en_passant % 8) as usize];
+    }
+
+    if !game_state.white_turn {
+        key ^= BLACK_TO_MOVE_KEY;
+    }
+
+    return key;
+}
+
+/// Computes the key from scratch. `Board` keeps its `hash` up to date incrementally,
+/// this is for setting it up and for checking it.
+pub fn hash(board: &Board) -> u64 {
+    let mut key = state_key(&board.game_state);
+
+    for square in 0..64 {
+        if let Some(piece) = board.piece_at(square) {
+            let white = board.white_pieces & (1 << square) > 0;
+            key ^= piece_key(piece, white, square);
+        }
+    }
+
+    return key;
+}
+
+/// Computes the key of only the pawns from scratch, like `hash`.
+pub fn pawn_hash(board: &Board) -> u64 {
+    let mut key = 0;
+    let mut pawns = board.pieces[Pieces::Pawns as usize];
+
+    while pawns > 0 {
+        let square = pawns.trailing_zeros();
+        let white = board.white_pieces & (1 << square) > 0;
+        key ^= piece_key(Pieces::Pawns, white, square);
+
+        pawns &= pawns - 1;
+    }
+
+    return key;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        board::START_FEN, move_generation::legal_moves, r#move::Move, test_util::next_random,
+    };
+
+    fn play(board: &mut Board, moves: &[&str]) {
+        for &name in moves {
+            let r#move = legal_moves(board)
+                .into_iter()
+                .find(|r#move| r#move.to_string() == name)
+                .unwrap();
+            board.make_move(&r#move);
+        }
+    }
+
+    #[test]
+    fn keys_are_distinct() {
+        let mut keys: Vec<u64> = PIECE_SQUARE_KEYS.to_vec();
+        keys.extend(CASTLING_KEYS);
+        keys.extend(EN_PASSANT_FILE_KEYS);
+        keys.push(BLACK_TO_MOVE_KEY);
+
+        let count = keys.len();
+        keys.sort();
+        keys.dedup();
+        assert_eq!(count, keys.len());
+    }
+
+    #[test]
+    fn transpositions_share_a_key() {
+        let mut board = Board::from_fen(START_FEN).unwrap();
+        play(&mut board, &["g1f3", "g8f6", "b1c3", "b8c6"]);
+
+        let mut other = Board::from_fen(START_FEN).unwrap();
+        play(&mut other, &["b1c3", "b8c6", "g1f3", "g8f6"]);
+
+        assert_eq!(board.hash, other.hash);
+        assert_eq!(board.hash, hash(&board));
+
+        let mut moved_back = Board::from_fen(START_FEN).unwrap();
+        play(&mut moved_back, &["g1f3", "g8f6", "f3g1", "f6g8"]);
+        assert_eq!(Board::from_fen(START_FEN).unwrap().hash, moved_back.hash);
+    }
+
+    #[test]
+    fn pawn_key_only_follows_pawns() {
+        let mut board = Board::from_fen(START_FEN).unwrap();
+        let start = board.pawn_hash;
+
+        play(&mut board, &["g1f3", "g8f6"]);
+        assert_eq!(start, board.pawn_hash);
+
+        play(&mut board, &["e2e4"]);
+        assert_ne!(start, board.pawn_hash);
+        assert_eq!(pawn_hash(&board), board.pawn_hash);
+    }
+
+    #[test]
+    fn state_changes_the_key() {
+        let board = Board::from_fen(START_FEN).unwrap();
+
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w Qkq - 0 1",
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq e3 0 1",
+        ] {
+            assert_ne!(board.hash, Board::from_fen(fen).unwrap().hash, "{fen}");
+        }
+    }
+
+    #[test]
+    fn incremental_key_matches_full_hash() {
+        let mut board =
+            Board::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1")
+                .unwrap();
+        let mut random_state: u64 = 0x2545F4914F6CDD1D;
+        let mut history: Vec<(Move, u64)> = Vec::new();
+
+        for _ in 0..200 {
+            let moves = legal_moves(&board);
+            if moves.is_empty() {
+                break;
+            }
+
+            let r#move = moves[next_random(&mut random_state) as usize % moves.len()];
+
+            history.push((r#move, board.hash));
+            let undo = board.make_move(&r#move);
+            assert_eq!(hash(&board), board.hash, "after {}", r#move);
+            assert_eq!(pawn_hash(&board), board.pawn_hash, "after {}", r#move);
+
+            // Check that taking the move back restores the key as well
+            board.unmake_move(&r#move, undo);
+            assert_eq!(history.last().unwrap().1, board.hash);
+            board.make_move(&r#move);
+        }
+    }
+}